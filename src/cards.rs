@@ -0,0 +1,283 @@
+// Copyright 2026 YuzakiKokuban
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Built-in rich renderings of the run report for chat platforms that
+//! support markdown or interactive cards.
+
+use serde_json::Value;
+
+use crate::report::{AccountReport, SignRecord, SignStatus};
+use crate::utils::object_from_pairs;
+
+/// How a platform's markdown dialect colors text.
+#[derive(Clone, Copy)]
+enum ColorStyle {
+    /// DingTalk: `<font color=#rrggbb>`.
+    Hex,
+    /// WeCom: `<font color="info|comment|warning">`.
+    WeCom,
+    /// Feishu `lark_md`: `<font color='green|grey|red|orange'>`.
+    Feishu,
+}
+
+pub fn dingtalk_markdown(title: &str, accounts: &[&AccountReport]) -> String {
+    render_markdown(title, accounts, ColorStyle::Hex)
+}
+
+pub fn wecom_markdown(title: &str, accounts: &[&AccountReport]) -> String {
+    render_markdown(title, accounts, ColorStyle::WeCom)
+}
+
+/// A Feishu interactive card: a colored header, a summary line and one
+/// section per account separated by rules.
+pub fn feishu_card(title: &str, accounts: &[&AccountReport], mention_all: bool) -> Value {
+    let all_success = accounts.iter().all(|account| account.success);
+    let mut elements = Vec::new();
+    if mention_all {
+        elements.push(lark_md_div("<at id=all></at>".to_string()));
+    }
+    elements.push(lark_md_div(summary_line(accounts)));
+    for account in accounts {
+        elements.push(object_from_pairs([(
+            "tag",
+            Value::String("hr".to_string()),
+        )]));
+        elements.push(lark_md_div(account_section(account, ColorStyle::Feishu)));
+    }
+
+    object_from_pairs([
+        ("msg_type", Value::String("interactive".to_string())),
+        (
+            "card",
+            object_from_pairs([
+                (
+                    "config",
+                    object_from_pairs([("wide_screen_mode", Value::Bool(true))]),
+                ),
+                (
+                    "header",
+                    object_from_pairs([
+                        (
+                            "template",
+                            Value::String(if all_success { "green" } else { "red" }.to_string()),
+                        ),
+                        (
+                            "title",
+                            object_from_pairs([
+                                ("tag", Value::String("plain_text".to_string())),
+                                ("content", Value::String(title.to_string())),
+                            ]),
+                        ),
+                    ]),
+                ),
+                ("elements", Value::Array(elements)),
+            ]),
+        ),
+    ])
+}
+
+fn lark_md_div(content: String) -> Value {
+    object_from_pairs([
+        ("tag", Value::String("div".to_string())),
+        (
+            "text",
+            object_from_pairs([
+                ("tag", Value::String("lark_md".to_string())),
+                ("content", Value::String(content)),
+            ]),
+        ),
+    ])
+}
+
+fn render_markdown(title: &str, accounts: &[&AccountReport], style: ColorStyle) -> String {
+    let mut sections = vec![
+        format!("### {title}"),
+        format!("> {}", summary_line(accounts)),
+    ];
+    for account in accounts {
+        sections.push("---".to_string());
+        sections.push(account_section(account, style));
+    }
+    sections.join("\n\n")
+}
+
+fn summary_line(accounts: &[&AccountReport]) -> String {
+    let records = accounts
+        .iter()
+        .flat_map(|account| account.records.iter())
+        .collect::<Vec<_>>();
+    let count = |status: SignStatus| {
+        records
+            .iter()
+            .filter(|record| record.status == status)
+            .count()
+    };
+    let failed_accounts = accounts.iter().filter(|account| !account.success).count();
+
+    let mut line = format!(
+        "✅ 成功 {} · ℹ️ 已签 {} · ❌ 失败 {}",
+        count(SignStatus::Success),
+        count(SignStatus::Info),
+        count(SignStatus::Fail) + count(SignStatus::Error),
+    );
+    let challenges = count(SignStatus::Challenge)
+        + accounts
+            .iter()
+            .filter(|account| account.challenge.is_some())
+            .count();
+    if challenges > 0 {
+        line.push_str(&format!(" · 🧩 验证 {challenges}"));
+    }
+    line.push_str(&format!(
+        " · 👤 账号 {}/{}",
+        accounts.len() - failed_accounts,
+        accounts.len()
+    ));
+    line
+}
+
+fn account_section(account: &AccountReport, style: ColorStyle) -> String {
+    let mut lines = vec![format!("**👤 {}**", account.label)];
+    if account.token_expired() {
+        lines.push(format!(
+            "- {}",
+            colored(
+                &format!("⚠️ {} 已过期，请尽快更新", account.credential_kind),
+                SignStatus::Fail,
+                style
+            )
+        ));
+    }
+    for record in &account.records {
+        lines.push(record_line(record, style));
+    }
+    for event in &account.events {
+        lines.push(format!(
+            "- {}",
+            colored(&format!("🔄 {event}"), SignStatus::Info, style)
+        ));
+    }
+    if let Some(challenge) = &account.challenge {
+        lines.push(format!(
+            "- {}",
+            colored(
+                &format!("🧩 登录需要验证（{}）", challenge.kind),
+                SignStatus::Challenge,
+                style
+            )
+        ));
+    }
+    if let Some(error) = &account.error {
+        lines.push(format!("- {}", colored(error, SignStatus::Error, style)));
+    }
+    lines.join("\n")
+}
+
+fn record_line(record: &SignRecord, style: ColorStyle) -> String {
+    let mut line = format!(
+        "- [{}] {}: {} {}",
+        record.app_code.to_uppercase(),
+        record.display_name,
+        colored(record.status.as_str(), record.status, style),
+        record.message
+    );
+    for award in &record.awards {
+        line.push_str(&format!("\n  - 🎁 {} ×{}", award.name, award.count));
+    }
+    line
+}
+
+fn colored(text: &str, status: SignStatus, style: ColorStyle) -> String {
+    match style {
+        ColorStyle::Hex => {
+            let color = match status {
+                SignStatus::Success => "#52c41a",
+                SignStatus::Info => "#1890ff",
+                SignStatus::Fail | SignStatus::Error => "#f5222d",
+                SignStatus::Challenge => "#fa8c16",
+            };
+            format!("<font color={color}>{text}</font>")
+        }
+        ColorStyle::WeCom => {
+            let color = match status {
+                SignStatus::Success => "info",
+                SignStatus::Info => "comment",
+                SignStatus::Fail | SignStatus::Error | SignStatus::Challenge => "warning",
+            };
+            format!("<font color=\"{color}\">{text}</font>")
+        }
+        ColorStyle::Feishu => {
+            let color = match status {
+                SignStatus::Success => "green",
+                SignStatus::Info => "grey",
+                SignStatus::Fail | SignStatus::Error => "red",
+                SignStatus::Challenge => "orange",
+            };
+            format!("<font color='{color}'>{text}</font>")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::Award;
+
+    fn account() -> AccountReport {
+        let mut account = AccountReport::new(1, "main");
+        account.push_record(SignRecord {
+            app_code: "arknights".to_string(),
+            display_name: "Amiya".to_string(),
+            status: SignStatus::Success,
+            message: "签到成功".to_string(),
+            awards: vec![Award {
+                name: "龙门币".to_string(),
+                count: "500".to_string(),
+            }],
+            challenge: None,
+        });
+        account.push_record(SignRecord {
+            app_code: "endfield".to_string(),
+            display_name: "Endmin".to_string(),
+            status: SignStatus::Fail,
+            message: "服务器错误".to_string(),
+            awards: Vec::new(),
+            challenge: None,
+        });
+        account
+    }
+
+    #[test]
+    fn renders_each_platform_style() {
+        let account = account();
+        let accounts = [&account];
+
+        let dingtalk = dingtalk_markdown("森空岛签到", &accounts);
+        assert_eq!(
+            dingtalk,
+            "### 森空岛签到\n\n\
+             > ✅ 成功 1 · ℹ️ 已签 0 · ❌ 失败 1 · 👤 账号 0/1\n\n\
+             ---\n\n\
+             **👤 main**\n\
+             - [ARKNIGHTS] Amiya: <font color=#52c41a>SUCCESS</font> 签到成功\n\
+             \x20 - 🎁 龙门币 ×500\n\
+             - [ENDFIELD] Endmin: <font color=#f5222d>FAIL</font> 服务器错误"
+        );
+
+        let wecom = wecom_markdown("森空岛签到", &accounts);
+        assert!(wecom.contains("<font color=\"info\">SUCCESS</font>"));
+        assert!(wecom.contains("<font color=\"warning\">FAIL</font> 服务器错误"));
+
+        let feishu = feishu_card("森空岛签到", &accounts, false);
+        assert_eq!(feishu["msg_type"], "interactive");
+        assert_eq!(feishu["card"]["header"]["template"], "red");
+        assert_eq!(feishu["card"]["header"]["title"]["content"], "森空岛签到");
+        let elements = feishu["card"]["elements"].as_array().unwrap();
+        assert_eq!(elements.len(), 3);
+        assert_eq!(elements[1]["tag"], "hr");
+        let section = elements[2]["text"]["content"].as_str().unwrap();
+        assert!(section.starts_with("**👤 main**\n"));
+        assert!(section.contains("<font color='green'>SUCCESS</font>"));
+        assert!(section.contains("<font color='red'>FAIL</font>"));
+    }
+}
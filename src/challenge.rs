@@ -0,0 +1,302 @@
+// Copyright 2026 YuzakiKokuban
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Captcha and risk-control responses. Skyland and Hypergryph answer a
+//! suspicious request with geetest or captcha parameters in `data` or, in
+//! front of the API, a WAF page instead of JSON. `send_json` turns these
+//! into a `ChallengeError` so they are reported as `CHALLENGE` rather than
+//! a generic failure, with the details kept for whoever has to deal with
+//! them. JSON answers are only judged by their structure: messages such as
+//! "验证码错误" for a mistyped SMS code are ordinary errors.
+//!
+//! Risk-control rejections that carry no captcha parameters are not told
+//! apart from other errors, since Skyland gives them no distinct structure.
+//!
+//! `CHALLENGE_HOOK` names a shell command that gets the challenge as JSON
+//! on stdin: an external solver, or something that pages a human. Exiting
+//! 0 means the challenge was dealt with and the call is retried once. A
+//! solver passes its result back by printing a `Solution` as JSON, which
+//! the retry carries; anything else it prints is only logged.
+
+use std::io::Write;
+use std::process::Stdio;
+
+use anyhow::{Context, Result, bail};
+use serde_json::{Map, Number, Value};
+use tracing::{debug, info, warn};
+
+use crate::config::non_empty_env;
+use crate::utils::{object_from_pairs, python_json_string, shell_command};
+
+/// Words that give away a captcha page served instead of JSON.
+const PAGE_MARKERS: [&str; 7] = [
+    "风控",
+    "风险",
+    "验证码",
+    "人机验证",
+    "安全验证",
+    "captcha",
+    "geetest",
+];
+/// Statuses WAFs use for their captcha pages.
+const PAGE_STATUSES: [u16; 2] = [405, 412];
+/// How much of a non-JSON page to keep.
+const PAGE_EXCERPT_CHARS: usize = 500;
+
+#[derive(Clone, Debug)]
+pub struct Challenge {
+    pub action: String,
+    /// `geetest`, `captcha` or `page`.
+    pub kind: &'static str,
+    pub code: Option<i64>,
+    pub message: String,
+    /// The captcha parameters, the response `data`, or a page excerpt.
+    pub details: Value,
+}
+
+/// What a hook printed for the retry: `{"headers": {...}, "body": {...}}`,
+/// both optional. Headers are added to the retried request and body fields
+/// merged into its JSON body, such as a geetest `validate`/`seccode`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Solution {
+    pub headers: Vec<(String, String)>,
+    pub body: Map<String, Value>,
+}
+
+/// Raised by `send_json` for a challenge response.
+#[derive(Debug)]
+pub struct ChallengeError(pub Challenge);
+
+impl std::fmt::Display for ChallengeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} blocked by {} challenge: {}",
+            self.0.action, self.0.kind, self.0.message
+        )
+    }
+}
+
+impl std::error::Error for ChallengeError {}
+
+impl Challenge {
+    pub fn to_value(&self) -> Value {
+        object_from_pairs([
+            ("action", Value::String(self.action.clone())),
+            ("kind", Value::String(self.kind.to_string())),
+            (
+                "code",
+                self.code
+                    .map(|code| Value::Number(Number::from(code)))
+                    .unwrap_or(Value::Null),
+            ),
+            ("message", Value::String(self.message.clone())),
+            ("details", self.details.clone()),
+        ])
+    }
+}
+
+/// Recognises captcha parameters in a parsed Skyland (`code`/`message`) or
+/// Hypergryph (`status`/`msg`) response. Successful answers never count.
+pub fn detect(action: &str, response: &Value) -> Option<Challenge> {
+    let code = response
+        .get("code")
+        .or_else(|| response.get("status"))
+        .and_then(Value::as_i64);
+    if code == Some(0) {
+        return None;
+    }
+    let message = response
+        .get("message")
+        .or_else(|| response.get("msg"))
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let data = response.get("data").unwrap_or(&Value::Null);
+
+    let captcha = ["captcha", "geetest"]
+        .into_iter()
+        .find_map(|key| data.get(key).filter(|value| !value.is_null()));
+    let (kind, details) = if let Some(captcha) = captcha {
+        let geetest = data.get("geetest").is_some() || captcha.get("gt").is_some();
+        (if geetest { "geetest" } else { "captcha" }, captcha.clone())
+    } else if data.get("gt").is_some() && data.get("challenge").is_some() {
+        ("geetest", data.clone())
+    } else {
+        return None;
+    };
+
+    Some(Challenge {
+        action: action.to_string(),
+        kind,
+        code,
+        message: if message.is_empty() {
+            format!("{kind} challenge")
+        } else {
+            message.to_string()
+        },
+        details,
+    })
+}
+
+/// Recognises a captcha or WAF page served instead of JSON.
+pub fn detect_page(action: &str, status: u16, body: &str) -> Option<Challenge> {
+    let lowered = body.to_lowercase();
+    let marked = PAGE_MARKERS.iter().any(|marker| lowered.contains(marker));
+    if !marked && !PAGE_STATUSES.contains(&status) {
+        return None;
+    }
+    Some(Challenge {
+        action: action.to_string(),
+        kind: "page",
+        code: Some(i64::from(status)),
+        message: format!("HTTP {status} with a non-JSON page"),
+        details: object_from_pairs([
+            ("status", Value::Number(Number::from(status))),
+            (
+                "body",
+                Value::String(body.chars().take(PAGE_EXCERPT_CHARS).collect()),
+            ),
+        ]),
+    })
+}
+
+/// Hands `challenge` to `CHALLENGE_HOOK`, if set, and returns what to retry
+/// with when the hook reported it dealt with.
+pub fn hand_off(challenge: &Challenge, account: &str) -> Option<Solution> {
+    let command = non_empty_env("CHALLENGE_HOOK")?;
+    match run_hook(&command, challenge, account) {
+        Ok(Some(solution)) => {
+            info!(
+                kind = challenge.kind,
+                headers = solution.headers.len(),
+                fields = solution.body.len(),
+                "Challenge hook resolved {}",
+                challenge.action
+            );
+            Some(solution)
+        }
+        Ok(None) => {
+            warn!(
+                kind = challenge.kind,
+                "Challenge hook gave up on {}", challenge.action
+            );
+            None
+        }
+        Err(error) => {
+            warn!("Challenge hook Error: {error:#}");
+            None
+        }
+    }
+}
+
+fn run_hook(command: &str, challenge: &Challenge, account: &str) -> Result<Option<Solution>> {
+    let mut payload = challenge.to_value();
+    if let Value::Object(map) = &mut payload {
+        map.insert("account".to_string(), Value::String(account.to_string()));
+    }
+
+    let mut child = shell_command(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("failed to start CHALLENGE_HOOK")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that ignores stdin may exit before reading it; its exit
+        // status still decides.
+        let _ = stdin.write_all(python_json_string(&payload).as_bytes());
+    }
+    let output = child
+        .wait_with_output()
+        .context("failed to wait for CHALLENGE_HOOK")?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    parse_solution(&String::from_utf8_lossy(&output.stdout)).map(Some)
+}
+
+/// A JSON object on stdout is a `Solution`; other output means the hook
+/// changed something outside the request, and is logged.
+fn parse_solution(stdout: &str) -> Result<Solution> {
+    let stdout = stdout.trim();
+    let Ok(Value::Object(mut output)) = serde_json::from_str::<Value>(stdout) else {
+        if !stdout.is_empty() {
+            info!("Challenge hook: {stdout}");
+        }
+        return Ok(Solution::default());
+    };
+    // Solutions are tokens; keep them out of normal logs.
+    debug!("Challenge hook solution: {stdout}");
+
+    let mut solution = Solution::default();
+    match output.remove("headers") {
+        None | Some(Value::Null) => {}
+        Some(Value::Object(headers)) => {
+            for (name, value) in headers {
+                let Value::String(value) = value else {
+                    bail!("CHALLENGE_HOOK header {name} must be a string");
+                };
+                solution.headers.push((name, value));
+            }
+        }
+        Some(_) => bail!("CHALLENGE_HOOK headers must be a JSON object"),
+    }
+    match output.remove("body") {
+        None | Some(Value::Null) => {}
+        Some(Value::Object(body)) => solution.body = body,
+        Some(_) => bail!("CHALLENGE_HOOK body must be a JSON object"),
+    }
+    Ok(solution)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_captcha_structures_not_messages() {
+        let geetest = serde_json::json!({
+            "status": 1,
+            "msg": "请完成验证",
+            "data": {"captcha": {"gt": "abc", "challenge": "def"}},
+        });
+        let challenge = detect("password login", &geetest).unwrap();
+        assert_eq!(challenge.kind, "geetest");
+        assert_eq!(challenge.details["gt"], "abc");
+
+        let wrong_code = serde_json::json!({"status": 1, "msg": "验证码错误", "data": null});
+        assert!(detect("Phone code login", &wrong_code).is_none());
+        let rate_limited =
+            serde_json::json!({"status": 1, "msg": "发送验证码过于频繁，请稍后再试"});
+        assert!(detect("Send phone code", &rate_limited).is_none());
+
+        let repeated = serde_json::json!({"code": 10001, "message": "请勿重复签到！"});
+        assert!(detect("sign", &repeated).is_none());
+        let ok = serde_json::json!({"code": 0, "message": "OK", "data": {"captcha": null}});
+        assert!(detect("sign", &ok).is_none());
+
+        assert!(detect_page("sign", 405, "<html>Access denied</html>").is_some());
+        assert!(detect_page("sign", 502, "<html>Bad Gateway</html>").is_none());
+    }
+
+    #[test]
+    fn parses_hook_solutions() {
+        assert_eq!(parse_solution("").unwrap(), Solution::default());
+        assert_eq!(
+            parse_solution("paged the owner\n").unwrap(),
+            Solution::default()
+        );
+
+        let solution = parse_solution(
+            r#"{"headers": {"X-Captcha-Token": "tok"}, "body": {"seccode": "abc|jordan"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            solution.headers,
+            [("X-Captcha-Token".to_string(), "tok".to_string())]
+        );
+        assert_eq!(solution.body["seccode"], "abc|jordan");
+
+        assert!(parse_solution(r#"{"headers": {"X-Captcha-Token": 1}}"#).is_err());
+        assert!(parse_solution(r#"{"body": "seccode"}"#).is_err());
+    }
+}
@@ -0,0 +1,599 @@
+// Copyright 2026 YuzakiKokuban
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::Local;
+use reqwest::blocking::Client as HttpClient;
+use serde_json::{Map, Value};
+use tracing::{error, info, info_span, warn};
+
+use crate::client::{SkylandClient, TokenHealth};
+use crate::config::{
+    Account, Credential, NotifyTarget, load_accounts, load_notify_targets, non_empty_env,
+};
+use crate::fingerprint::{DeviceProfiles, load_device_profiles};
+use crate::notifier::{expired_accounts, notify, notify_token_expired, select_reports};
+use crate::pacing::{Pacing, load_pacing};
+use crate::platform::{Platforms, load_platforms};
+use crate::redact::outln;
+use crate::report::{AccountReport, RunReport};
+use crate::secrets::secret_env;
+use crate::security::{
+    DeviceProfilePayload, ShumeiSdk, build_device_profile, decrypt_device_profile_data,
+    load_shumei_sdk, undo_des_transform,
+};
+use crate::state::RunState;
+use crate::system::{SystemClock, SystemRandom};
+use crate::utils::{env_flag, mask_token, prompt, prompt_secret, python_json_string};
+use crate::vault::{Vault, vault_passphrase, vault_path, write_private};
+use crate::{logging, metrics, redact};
+
+const USAGE: &str = "\
+Usage: skas [-v|--verbose] [run|check]
+       skas login [--sms] [--phone PHONE] [--output FILE]
+       skas vault add LABEL [--cred] | list | remove LABEL | rotate
+       skas device inspect [--profile NAME | --account LABEL]
+       skas device decode --pri-id ID [--data HEX|BODY]
+       skas api METHOD PATH [--query KEY=VALUE]... [--body JSON] [--account LABEL]";
+
+/// Entry point of the `skas` binary.
+pub fn main() {
+    let mut args = env::args().skip(1).collect::<Vec<_>>();
+    let verbose = args.iter().any(|arg| arg == "-v" || arg == "--verbose");
+    args.retain(|arg| arg != "-v" && arg != "--verbose");
+    logging::init(verbose);
+
+    match args.first().map(String::as_str) {
+        None | Some("run") => run(),
+        Some("check") => check(),
+        Some("login") => login(&args[1..]),
+        Some("vault") => vault(&args[1..]),
+        Some("device") => device(&args[1..]),
+        Some("api") => api(&args[1..]),
+        Some("-h" | "--help" | "help") => println!("{USAGE}"),
+        Some(other) => {
+            eprintln!("Unknown command: {other}\n{USAGE}");
+            process::exit(2);
+        }
+    }
+}
+
+fn run() {
+    let accounts = require_accounts();
+
+    let enable_games = env::var("ENABLE_GAMES")
+        .unwrap_or_else(|_| "arknights,endfield".to_string())
+        .split(',')
+        .map(str::trim)
+        .filter(|game| !game.is_empty())
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+
+    let targets = require_notify_targets();
+    let devices = require_device_profiles();
+    let platforms = require_platforms();
+    let sdk = require_shumei_sdk();
+    let pacing = require_pacing();
+    let state = RunState::load(non_empty_env("NOTIFY_STATE_FILE"));
+    let webhook_strict = env_flag("WEBHOOK_STRICT");
+    let webhook_client = build_webhook_client();
+
+    let mut run = RunReport::new();
+    let mut global_success = true;
+
+    for (index, account) in accounts.iter().enumerate() {
+        let _span = info_span!("account", index = index + 1, label = %account.label).entered();
+        pacing.stagger(&mut SystemRandom);
+        info!(
+            "Processing Account {} ({}): {}",
+            index + 1,
+            account.label,
+            mask_token(account.credential.secret())
+        );
+        let mut report = AccountReport::new(index + 1, account.label.clone());
+        report.credential_kind = account.credential.kind_name();
+
+        let client = devices.for_account(&account.label).and_then(|device| {
+            SkylandClient::from_credential(
+                &account.credential,
+                platforms.for_account(&account.label)?,
+                &sdk,
+                &device,
+            )
+            .map(|client| client.with_pacing(pacing))
+        });
+        match client {
+            Ok(mut client) => client.run_sign(&enable_games, &mut report),
+            Err(error) => {
+                report.fail(format!("Init Error: {error}"));
+            }
+        }
+
+        report.finish();
+        if !report.success {
+            global_success = false;
+        }
+        run.accounts.push(report);
+    }
+    run.finished_at = Local::now();
+
+    if !send_token_alerts(&webhook_client, &targets, &run.accounts) && webhook_strict {
+        global_success = false;
+    }
+
+    for target in &targets {
+        let selected = select_reports(target, &run.accounts, &state);
+        if selected.iter().all(|report| report.logs.is_empty()) {
+            continue;
+        }
+
+        match notify(&webhook_client, target, &run, &selected) {
+            Ok(()) => info!("Notify [{}] delivered", target.name),
+            Err(error) => {
+                error!("Notify [{}] Error: {error:#}", target.name);
+                if webhook_strict {
+                    global_success = false;
+                }
+            }
+        }
+    }
+
+    if let Err(error) = state.save(&run.accounts) {
+        warn!("State Error: {error:#}");
+    }
+    if let Err(error) = metrics::push(&webhook_client, &run) {
+        warn!("Metrics Error: {error:#}");
+    }
+
+    if !global_success {
+        process::exit(1);
+    }
+}
+
+/// Only asks Hypergryph whether each token is still usable, without signing
+/// in. Expired tokens trigger the same alerts as a full run.
+fn check() {
+    let accounts = require_accounts();
+    let targets = require_notify_targets();
+    let devices = require_device_profiles();
+    let platforms = require_platforms();
+    let sdk = require_shumei_sdk();
+    let webhook_client = build_webhook_client();
+
+    let mut reports = Vec::new();
+    let mut all_valid = true;
+    for (index, account) in accounts.iter().enumerate() {
+        let mut report = AccountReport::new(index + 1, account.label.clone());
+        report.credential_kind = account.credential.kind_name();
+        let result = devices
+            .for_account(&account.label)
+            .and_then(|device| {
+                SkylandClient::from_credential(
+                    &account.credential,
+                    platforms.for_account(&account.label)?,
+                    &sdk,
+                    &device,
+                )
+            })
+            .and_then(|client| client.check_token());
+
+        match result {
+            Ok((health, message)) => {
+                report.token_health = Some(health);
+                if health == TokenHealth::Valid {
+                    report.logs.push(format!("Token: {}", health.as_str()));
+                } else {
+                    report.fail(format!("Token: {} - {message}", health.as_str()));
+                }
+            }
+            Err(error) => report.fail(format!(
+                "Token: {} - {error}",
+                TokenHealth::Unknown.as_str()
+            )),
+        }
+
+        if !report.success {
+            all_valid = false;
+        }
+        outln!(
+            "Account {} ({}): {}",
+            index + 1,
+            account.label,
+            report.logs.join(" | ")
+        );
+        report.finish();
+        reports.push(report);
+    }
+
+    send_token_alerts(&webhook_client, &targets, &reports);
+
+    if !all_valid {
+        process::exit(1);
+    }
+}
+
+/// Obtains a Hypergryph token by phone + password, or by phone + SMS code
+/// with `--sms`. The password may come from `HYPERGRYPH_PASSWORD`; anything
+/// else missing is prompted for on stderr and read from stdin. The token is
+/// printed alone on stdout, or written to `--output`.
+fn login(args: &[String]) {
+    let result = (|| -> anyhow::Result<()> {
+        let sms = args.iter().any(|arg| arg == "--sms");
+        let output = option_value(args, "--output");
+        let phone = match option_value(args, "--phone") {
+            Some(phone) => phone,
+            None => prompt("Phone: ")?,
+        };
+
+        let client = SkylandClient::for_login(&load_shumei_sdk()?)?;
+        let token = if sms {
+            client.send_phone_code(&phone)?;
+            info!("Verification code sent to {}", mask_token(&phone));
+            let code = prompt("Code: ")?;
+            client.login_by_phone_code(&phone, &code)?
+        } else {
+            let password = match secret_env("HYPERGRYPH_PASSWORD")? {
+                Some(password) => password,
+                None => prompt_secret("Password: ")?,
+            };
+            redact::register(&password);
+            client.login_by_password(&phone, &password)?
+        };
+
+        match output {
+            Some(path) => {
+                write_private(Path::new(&path), format!("{token}\n").as_bytes())?;
+                info!("Token saved to {path}: {}", mask_token(&token));
+            }
+            None => println!("{token}"),
+        }
+        Ok(())
+    })();
+
+    if let Err(error) = result {
+        error!("Login Error: {error:#}");
+        process::exit(1);
+    }
+}
+
+/// Manages the encrypted account vault at `VAULT_FILE`. Tokens and the
+/// passphrase are prompted for unless `VAULT_PASSPHRASE` is set; `add`
+/// creates the vault on first use.
+fn vault(args: &[String]) {
+    let result = (|| -> anyhow::Result<()> {
+        let path = vault_path().context("VAULT_FILE is not set")?;
+        let command = args.first().map(String::as_str).unwrap_or_default();
+        let label = || {
+            args.get(1)
+                .filter(|label| !label.starts_with("--"))
+                .cloned()
+                .with_context(|| format!("vault {command} needs a LABEL"))
+        };
+
+        match command {
+            "add" => {
+                let label = label()?;
+                let mut vault = if fs::metadata(&path).is_ok() {
+                    Vault::open(&path, &vault_passphrase(false)?)?
+                } else {
+                    Vault::create(&path, &vault_passphrase(true)?)
+                };
+                let credential = if args.iter().any(|arg| arg == "--cred") {
+                    Credential::Cred {
+                        cred: prompt_secret("Cred: ")?,
+                        token: prompt_secret("Cred token: ")?,
+                    }
+                } else {
+                    Credential::Token(prompt_secret("Token: ")?)
+                };
+                let masked = mask_token(credential.secret());
+                let replaced = vault.upsert(Account {
+                    label: label.clone(),
+                    credential,
+                });
+                vault.save()?;
+                let action = if replaced { "Updated" } else { "Added" };
+                info!("{action} {label} ({masked}) in {}", vault.path().display());
+            }
+            "list" => {
+                let vault = Vault::open(&path, &vault_passphrase(false)?)?;
+                for account in &vault.accounts {
+                    let kind = match account.credential {
+                        Credential::Token(_) => "token",
+                        Credential::Cred { .. } => "cred",
+                    };
+                    outln!(
+                        "{}\t{kind}\t{}",
+                        account.label,
+                        mask_token(account.credential.secret())
+                    );
+                }
+            }
+            "remove" => {
+                let label = label()?;
+                let mut vault = Vault::open(&path, &vault_passphrase(false)?)?;
+                if !vault.remove(&label) {
+                    anyhow::bail!("no account {label} in vault");
+                }
+                vault.save()?;
+                info!("Removed {label} from {}", vault.path().display());
+            }
+            "rotate" => {
+                let mut vault = Vault::open(&path, &vault_passphrase(false)?)?;
+                let passphrase = match secret_env("VAULT_NEW_PASSPHRASE")? {
+                    Some(passphrase) => passphrase,
+                    None => {
+                        let passphrase = prompt_secret("New passphrase: ")?;
+                        if prompt_secret("Repeat new passphrase: ")? != passphrase {
+                            anyhow::bail!("passphrases do not match");
+                        }
+                        passphrase
+                    }
+                };
+                vault.rekey(&passphrase);
+                vault.save()?;
+                info!(
+                    "Re-encrypted {} with the new passphrase",
+                    vault.path().display()
+                );
+            }
+            other => anyhow::bail!("unknown vault command: {other:?}\n{USAGE}"),
+        }
+        Ok(())
+    })();
+
+    if let Err(error) = result {
+        error!("Vault Error: {error:#}");
+        process::exit(1);
+    }
+}
+
+/// Debugging aids for the Shumei device-profile request. `inspect` builds a
+/// request without sending it and prints every stage; `decode` decrypts a
+/// captured `data` field (or whole body) with its `pri_id`, so a browser's
+/// payload can be diffed against ours.
+fn device(args: &[String]) {
+    let result = (|| -> anyhow::Result<()> {
+        let sdk = load_shumei_sdk()?;
+        match args.first().map(String::as_str).unwrap_or_default() {
+            "inspect" => {
+                let devices = load_device_profiles()?;
+                let device = match option_value(args, "--account") {
+                    Some(label) => {
+                        if !load_accounts()?
+                            .iter()
+                            .any(|account| account.label == label)
+                        {
+                            anyhow::bail!("no account {label}");
+                        }
+                        devices.for_account(&label)?
+                    }
+                    None => devices.named(option_value(args, "--profile").as_deref())?,
+                };
+                let payload = build_device_profile(&sdk, &device, &SystemClock, &mut SystemRandom)?;
+                print_device_payload(&sdk, &device.name, &payload);
+            }
+            "decode" => {
+                let pri_id =
+                    option_value(args, "--pri-id").context("device decode needs --pri-id")?;
+                let raw = match option_value(args, "--data") {
+                    Some(data) => data,
+                    None => io::read_to_string(io::stdin()).context("failed to read stdin")?,
+                };
+                let data = if raw.trim_start().starts_with('{') {
+                    let body = serde_json::from_str::<Value>(&raw).context("invalid body JSON")?;
+                    body.get("data")
+                        .and_then(Value::as_str)
+                        .context("body has no data field")?
+                        .to_string()
+                } else {
+                    raw
+                };
+                let obfuscated = decrypt_device_profile_data(&data, &pri_id)?;
+                outln!("== Obfuscated map ==");
+                print_obfuscated(&sdk, &obfuscated);
+                outln!("== Decrypted fields ==");
+                print_fields(&undo_des_transform(&sdk, &obfuscated)?);
+            }
+            other => anyhow::bail!("unknown device command: {other:?}\n{USAGE}"),
+        }
+        Ok(())
+    })();
+
+    if let Err(error) = result {
+        error!("Device Error: {error:#}");
+        process::exit(1);
+    }
+}
+
+/// Calls a Skyland API as one account (the first unless `--account` says
+/// otherwise) and prints the `data` it returns.
+fn api(args: &[String]) {
+    let result = (|| -> anyhow::Result<()> {
+        let [method, path, ..] = args else {
+            anyhow::bail!("api needs METHOD and PATH\n{USAGE}");
+        };
+        let query = args
+            .windows(2)
+            .filter(|pair| pair[0] == "--query")
+            .map(|pair| {
+                pair[1]
+                    .split_once('=')
+                    .with_context(|| format!("--query {:?} is not KEY=VALUE", pair[1]))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let body = option_value(args, "--body")
+            .map(|raw| serde_json::from_str::<Value>(&raw).context("invalid --body JSON"))
+            .transpose()?;
+
+        let accounts = load_accounts()?;
+        let account = match option_value(args, "--account") {
+            Some(label) => accounts
+                .iter()
+                .find(|account| account.label == label)
+                .with_context(|| format!("no account {label}"))?,
+            None => accounts.first().context("no accounts configured")?,
+        };
+        let device = load_device_profiles()?.for_account(&account.label)?;
+        let mut client = SkylandClient::from_credential(
+            &account.credential,
+            load_platforms()?.for_account(&account.label)?,
+            &load_shumei_sdk()?,
+            &device,
+        )?
+        .with_label(&account.label);
+
+        let data = client.request(method, path, &query, body.as_ref())?;
+        outln!("{}", serde_json::to_string_pretty(&data)?);
+        Ok(())
+    })();
+
+    if let Err(error) = result {
+        error!("Api Error: {error:#}");
+        process::exit(1);
+    }
+}
+
+fn print_device_payload(sdk: &ShumeiSdk, profile: &str, payload: &DeviceProfilePayload) {
+    outln!("== Target map ({profile}, SDK {}) ==", sdk.sdkver);
+    print_fields(&payload.target);
+    outln!("== tn ==");
+    outln!("input: {}", payload.tn_input);
+    outln!("md5:   {}", payload.tn);
+    outln!("== Obfuscated map ==");
+    print_obfuscated(sdk, &payload.obfuscated);
+    outln!("== gzip + base64 ==");
+    outln!("{} bytes", payload.compressed.len());
+    outln!("== AES key (pri_id) ==");
+    outln!("{}", payload.pri_id);
+    outln!("== Request body ==");
+    outln!("{}", python_json_string(&payload.body));
+}
+
+fn print_fields(map: &Map<String, Value>) {
+    for (key, value) in map {
+        outln!("{key:<12} {}", python_json_string(value));
+    }
+}
+
+fn print_obfuscated(sdk: &ShumeiSdk, map: &Map<String, Value>) {
+    for (key, value) in map {
+        let original = sdk.original_name(key).unwrap_or("-");
+        outln!("{key:<12} {original:<12} {}", python_json_string(value));
+    }
+}
+
+fn option_value(args: &[String], name: &str) -> Option<String> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|index| args.get(index + 1))
+        .cloned()
+}
+
+/// Returns whether every alert was delivered.
+fn send_token_alerts(
+    http: &HttpClient,
+    targets: &[NotifyTarget],
+    reports: &[AccountReport],
+) -> bool {
+    let mut delivered = true;
+    for target in targets {
+        for account in expired_accounts(target, reports) {
+            match notify_token_expired(http, target, account) {
+                Ok(()) => info!(
+                    "Notify [{}] token alert for {} delivered",
+                    target.name, account.label
+                ),
+                Err(error) => {
+                    delivered = false;
+                    error!("Notify [{}] token alert Error: {error:#}", target.name);
+                }
+            }
+        }
+    }
+    delivered
+}
+
+fn require_accounts() -> Vec<Account> {
+    match load_accounts() {
+        Ok(accounts) if !accounts.is_empty() => accounts,
+        Ok(_) => {
+            error!("No tokens found in SKYLAND_TOKEN, SKYLAND_CRED or VAULT_FILE");
+            process::exit(1);
+        }
+        Err(error) => {
+            error!("Account config error: {error:#}");
+            process::exit(1);
+        }
+    }
+}
+
+fn require_notify_targets() -> Vec<NotifyTarget> {
+    match load_notify_targets() {
+        Ok(targets) => targets,
+        Err(error) => {
+            error!("Notifier config error: {error:#}");
+            process::exit(1);
+        }
+    }
+}
+
+fn require_device_profiles() -> DeviceProfiles {
+    match load_device_profiles() {
+        Ok(devices) => devices,
+        Err(error) => {
+            error!("Device profile config error: {error:#}");
+            process::exit(1);
+        }
+    }
+}
+
+fn require_platforms() -> Platforms {
+    match load_platforms() {
+        Ok(platforms) => platforms,
+        Err(error) => {
+            error!("Platform config error: {error:#}");
+            process::exit(1);
+        }
+    }
+}
+
+fn require_pacing() -> Pacing {
+    match load_pacing() {
+        Ok(pacing) => pacing,
+        Err(error) => {
+            error!("Pacing config error: {error:#}");
+            process::exit(1);
+        }
+    }
+}
+
+fn require_shumei_sdk() -> ShumeiSdk {
+    match load_shumei_sdk() {
+        Ok(sdk) => sdk,
+        Err(error) => {
+            error!("Shumei SDK config error: {error:#}");
+            process::exit(1);
+        }
+    }
+}
+
+fn build_webhook_client() -> HttpClient {
+    match HttpClient::builder()
+        .timeout(Duration::from_secs(15))
+        .build()
+    {
+        Ok(client) => client,
+        Err(error) => {
+            error!("Failed to build webhook client: {error}");
+            process::exit(1);
+        }
+    }
+}
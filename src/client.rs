@@ -1,29 +1,45 @@
 // Copyright 2026 YuzakiKokuban
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::cell::{Cell, RefCell};
 
-use anyhow::{Context, Result, anyhow};
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, FixedOffset};
 use hmac::digest::KeyInit;
 use hmac::{Hmac, Mac};
-use reqwest::Url;
 use reqwest::blocking::Client;
-use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Method, Url};
 use serde_json::Value;
 use sha2::Sha256;
+use tracing::{debug, info, info_span};
 
-use crate::security::get_d_id;
+use crate::challenge::{self, ChallengeError, Solution};
+use crate::config::Credential;
+use crate::fingerprint::DeviceProfile;
+use crate::logging::{send_json, send_json_dated};
+use crate::metrics;
+use crate::pacing::Pacing;
+use crate::platform::Platform;
+use crate::redact::register;
+use crate::report::{AccountReport, Award, SignRecord, SignStatus};
+use crate::security::{ShumeiSdk, get_d_id};
+use crate::system::{Clock, OffsetClock, RandomSource, SystemClock, SystemRandom};
 use crate::utils::{md5_hex, object_from_pairs, python_json_string};
 
 type HmacSha256 = Hmac<Sha256>;
 
 const APP_CODE: &str = "4ca99fa6b56cc2ba";
 const GRANT_CODE_URL: &str = "https://as.hypergryph.com/user/oauth2/v2/grant";
+const PASSWORD_LOGIN_URL: &str = "https://as.hypergryph.com/user/auth/v1/token_by_phone_password";
+const SEND_PHONE_CODE_URL: &str = "https://as.hypergryph.com/general/v1/send_phone_code";
+const PHONE_CODE_LOGIN_URL: &str = "https://as.hypergryph.com/user/auth/v2/token_by_phone_code";
 const CRED_CODE_URL: &str = "https://zonai.skland.com/web/v1/user/auth/generate_cred_by_code";
+const USER_CHECK_URL: &str = "https://zonai.skland.com/api/v1/user/check";
 const BINDING_URL: &str = "https://zonai.skland.com/api/v1/game/player/binding";
 const ARKNIGHTS_SIGN_URL: &str = "https://zonai.skland.com/api/v1/game/attendance";
 const ENDFIELD_SIGN_URL: &str = "https://zonai.skland.com/web/v1/game/endfield/attendance";
-const USER_AGENT: &str = "Mozilla/5.0 (Linux; Android 12; SKAS/1.0) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/101.0.4951.61 Mobile Safari/537.36";
+const SKYLAND_BASE_URL: &str = "https://zonai.skland.com";
 
 #[derive(Clone, Debug)]
 pub struct Character {
@@ -35,43 +51,310 @@ pub struct Character {
     pub server_id: Option<String>,
 }
 
+/// Skyland's `code` for a cred that is not (or no longer) logged in; 10001
+/// is "already signed" and must not trigger a refresh.
+const CRED_INVALID_CODE: i64 = 10002;
+/// Skyland's `code` for a signature timestamp too far from its clock
+/// ("请求已过期").
+const TIMESTAMP_ERROR_CODE: i64 = 10003;
+/// `Date` headers only carry whole seconds, so smaller differences from the
+/// local clock are noise.
+const MIN_SERVER_OFFSET_MS: i64 = 1_500;
+
 pub struct SkylandClient {
-    token: String,
+    /// `None` for accounts configured with a Skyland cred directly.
+    token: Option<String>,
     d_id: String,
     cred: Option<String>,
     cred_token: Option<String>,
     http: Client,
+    platform: Platform,
+    pacing: Pacing,
+    clock: Box<dyn Clock>,
+    /// Drives pacing; shared so signing can sample it from `&self`.
+    random: RefCell<Box<dyn RandomSource>>,
+    /// How far Skyland's clock is ahead of `clock`, from its `Date` headers.
+    server_offset_ms: Cell<i64>,
+    /// Notable things that happened behind the caller's back, such as cred
+    /// refreshes; see `take_events`.
+    events: Vec<String>,
+    /// The account `request` names to `CHALLENGE_HOOK`.
+    label: String,
+    /// What `CHALLENGE_HOOK` produced, applied to requests while
+    /// `with_challenge_hook` retries.
+    solution: Option<Solution>,
+}
+
+/// What the OAuth grant endpoint says about a Hypergryph token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenHealth {
+    Valid,
+    Expired,
+    Banned,
+    Unknown,
+}
+
+/// Raised by `login` when the grant is refused, carrying the classification
+/// so callers can tell an expired token from a transient failure.
+#[derive(Debug)]
+pub struct TokenError {
+    pub health: TokenHealth,
+    pub message: String,
+}
+
+/// A Skyland answer with a non-zero `code`, as returned by `request`.
+#[derive(Debug)]
+pub struct SkylandError {
+    pub path: String,
+    pub code: i64,
+    pub message: String,
+}
+
+impl std::fmt::Display for SkylandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} failed ({}): {}", self.path, self.code, self.message)
+    }
+}
+
+impl std::error::Error for SkylandError {}
+
+impl TokenHealth {
+    /// Hypergryph answers `status: 0` with a grant code for usable tokens;
+    /// otherwise only `msg` tells the cases apart.
+    pub fn classify(grant_response: &Value) -> Self {
+        if grant_response.get("status").and_then(Value::as_i64) == Some(0) {
+            return Self::Valid;
+        }
+
+        let message = grant_response
+            .get("msg")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_lowercase();
+        if ["封禁", "冻结", "禁用", "banned", "frozen"]
+            .iter()
+            .any(|keyword| message.contains(keyword))
+        {
+            Self::Banned
+        } else if ["过期", "重新登录", "失效", "expired", "invalid token"]
+            .iter()
+            .any(|keyword| message.contains(keyword))
+        {
+            Self::Expired
+        } else {
+            Self::Unknown
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Valid => "VALID",
+            Self::Expired => "EXPIRED",
+            Self::Banned => "BANNED",
+            Self::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+impl std::fmt::Display for TokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "OAuth Grant failed ({}): {}",
+            self.health.as_str(),
+            self.message
+        )
+    }
 }
 
+impl std::error::Error for TokenError {}
+
 struct SignatureHeaders {
     sign: String,
     timestamp: String,
 }
 
 impl SkylandClient {
-    pub fn new(token: impl Into<String>) -> Result<Self> {
+    /// Builds a client for a configured account posing as `platform`. Web
+    /// clients report `device` as their fingerprint in the format `sdk`
+    /// describes; Android ones send their Android ID instead. A
+    /// `cred`/`token` pair captured from the Skyland app is used as-is
+    /// instead of exchanging a Hypergryph token for one.
+    pub fn from_credential(
+        credential: &Credential,
+        platform: Platform,
+        sdk: &ShumeiSdk,
+        device: &DeviceProfile,
+    ) -> Result<Self> {
+        Self::from_credential_with(
+            credential,
+            platform,
+            sdk,
+            device,
+            Box::new(SystemClock),
+            Box::new(SystemRandom),
+        )
+    }
+
+    /// `from_credential` with the clock and randomness behind the device
+    /// profile, signature timestamps and pacing supplied by the caller.
+    pub fn from_credential_with(
+        credential: &Credential,
+        platform: Platform,
+        sdk: &ShumeiSdk,
+        device: &DeviceProfile,
+        clock: Box<dyn Clock>,
+        random: Box<dyn RandomSource>,
+    ) -> Result<Self> {
+        let (token, cred) = match credential {
+            Credential::Token(token) => (Some(token.clone()), None),
+            Credential::Cred { cred, token } => (None, Some((cred.clone(), token.clone()))),
+        };
+        Self::build(token, cred, platform, sdk, device, clock, random)
+    }
+
+    /// A client without a Hypergryph token, for the account login flows that
+    /// produce one.
+    pub fn for_login(sdk: &ShumeiSdk) -> Result<Self> {
+        Self::build(
+            None,
+            None,
+            Platform::Web,
+            sdk,
+            &DeviceProfile::default(),
+            Box::new(SystemClock),
+            Box::new(SystemRandom),
+        )
+    }
+
+    fn build(
+        token: Option<String>,
+        cred: Option<(String, String)>,
+        platform: Platform,
+        sdk: &ShumeiSdk,
+        device: &DeviceProfile,
+        clock: Box<dyn Clock>,
+        mut random: Box<dyn RandomSource>,
+    ) -> Result<Self> {
         let http = Client::builder()
             .use_rustls_tls()
             .gzip(true)
             .build()
             .context("failed to build HTTP client")?;
 
-        let d_id = get_d_id(&http)?;
+        let d_id = match &platform {
+            Platform::Web => {
+                debug!(profile = %device.name, "Requesting device profile");
+                get_d_id(&http, sdk, device, clock.as_ref(), random.as_mut())?
+            }
+            Platform::Android { android_id } => {
+                debug!(platform = platform.name(), "Using the Android ID as dId");
+                android_id.clone()
+            }
+        };
+        register(&d_id);
+        for secret in token
+            .iter()
+            .chain(cred.iter().flat_map(|(cred, token)| [cred, token]))
+        {
+            register(secret);
+        }
+        let (cred, cred_token) = cred.unzip();
         Ok(Self {
-            token: token.into(),
+            token,
             d_id,
-            cred: None,
-            cred_token: None,
+            cred,
+            cred_token,
             http,
+            platform,
+            pacing: Pacing::default(),
+            clock,
+            random: RefCell::new(random),
+            server_offset_ms: Cell::new(0),
+            events: Vec::new(),
+            label: String::new(),
+            solution: None,
         })
     }
 
-    pub fn run_sign(&mut self, enable_games: &[String]) -> (bool, Vec<String>) {
-        let mut logs = Vec::new();
-        let mut all_success = true;
+    /// Spaces out the calls in `run_sign` and varies signature timestamps
+    /// as `pacing` says.
+    pub(crate) fn with_pacing(mut self, pacing: Pacing) -> Self {
+        self.pacing = pacing;
+        self
+    }
+
+    /// Names the account to `CHALLENGE_HOOK` when `request` hits a check.
+    pub fn with_label(mut self, label: &str) -> Self {
+        self.label = label.to_string();
+        self
+    }
+
+    pub fn login_by_password(&self, phone: &str, password: &str) -> Result<String> {
+        let data = self.hypergryph_post(
+            PASSWORD_LOGIN_URL,
+            object_from_pairs([
+                ("phone", Value::String(phone.to_string())),
+                ("password", Value::String(password.to_string())),
+            ]),
+            "Password login",
+        )?;
+        token_from_login_data(&data)
+    }
 
+    pub fn send_phone_code(&self, phone: &str) -> Result<()> {
+        self.hypergryph_post(
+            SEND_PHONE_CODE_URL,
+            object_from_pairs([
+                ("phone", Value::String(phone.to_string())),
+                ("type", Value::Number(2.into())),
+            ]),
+            "Send phone code",
+        )?;
+        Ok(())
+    }
+
+    pub fn login_by_phone_code(&self, phone: &str, code: &str) -> Result<String> {
+        let data = self.hypergryph_post(
+            PHONE_CODE_LOGIN_URL,
+            object_from_pairs([
+                ("phone", Value::String(phone.to_string())),
+                ("code", Value::String(code.to_string())),
+            ]),
+            "Phone code login",
+        )?;
+        token_from_login_data(&data)
+    }
+
+    /// Posts to an `as.hypergryph.com` endpoint and unwraps its
+    /// `status`/`msg`/`data` envelope.
+    fn hypergryph_post(&self, url: &str, body: Value, action: &str) -> Result<Value> {
+        let response = send_json(
+            self.http
+                .post(url)
+                .headers(self.base_headers()?)
+                .header("Content-Type", "application/json")
+                .body(self.json_body(body)),
+            action,
+        )?;
+
+        if response.get("status").and_then(Value::as_i64) != Some(0) {
+            let message = response
+                .get("msg")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown error");
+            return Err(anyhow!("{action} failed: {message}"));
+        }
+
+        Ok(response.get("data").cloned().unwrap_or(Value::Null))
+    }
+
+    pub(crate) fn run_sign(&mut self, enable_games: &[String], report: &mut AccountReport) {
         let result = (|| -> Result<()> {
-            self.login()?;
+            self.with_challenge_hook(&report.label, Self::login)
+                .inspect_err(|_| metrics::record_login_failure())?;
+            self.pacing
+                .pause("bindings", self.random.get_mut().as_mut());
             for character in self.get_bindings()? {
                 if !enable_games.is_empty()
                     && !enable_games.iter().any(|game| game == &character.app_code)
@@ -79,13 +362,23 @@ impl SkylandClient {
                     continue;
                 }
 
-                let sign_result = match character.app_code.as_str() {
-                    "arknights" => self.sign_arknights(&character),
-                    "endfield" => self.sign_endfield(&character),
-                    _ => continue,
-                };
+                let _span = info_span!(
+                    "character",
+                    game = %character.app_code,
+                    name = %character.display_name
+                )
+                .entered();
+                let sign: fn(&mut Self, &Character) -> Result<Value> =
+                    match character.app_code.as_str() {
+                        "arknights" => Self::sign_arknights,
+                        "endfield" => Self::sign_endfield,
+                        _ => continue,
+                    };
+                self.pacing.pause("sign", self.random.get_mut().as_mut());
+                let sign_result =
+                    self.with_challenge_hook(&report.label, |client| sign(client, &character));
 
-                match sign_result {
+                let record = match sign_result {
                     Ok(result) => {
                         let code = result.get("code").and_then(Value::as_i64).unwrap_or(-1);
                         let msg = result
@@ -94,12 +387,11 @@ impl SkylandClient {
                             .unwrap_or("OK");
 
                         let status = if code == 0 {
-                            "SUCCESS"
+                            SignStatus::Success
                         } else if msg.contains("重复") {
-                            "INFO"
+                            SignStatus::Info
                         } else {
-                            all_success = false;
-                            "FAIL"
+                            SignStatus::Fail
                         };
 
                         let awards = result
@@ -108,7 +400,7 @@ impl SkylandClient {
                             .and_then(|data| data.get("awards"))
                             .and_then(Value::as_array)
                             .map(|awards| {
-                                let rendered = awards
+                                awards
                                     .iter()
                                     .filter_map(|award| {
                                         let resource_name = award
@@ -117,74 +409,172 @@ impl SkylandClient {
                                             .and_then(|resource| resource.get("name"))
                                             .and_then(Value::as_str)?;
                                         let count = award.get("count")?;
-                                        Some(format!(
-                                            "{resource_name}x{}",
-                                            render_plain_value(count)
-                                        ))
+                                        Some(Award {
+                                            name: resource_name.to_string(),
+                                            count: render_plain_value(count),
+                                        })
                                     })
-                                    .collect::<Vec<_>>();
-                                if rendered.is_empty() {
-                                    String::new()
-                                } else {
-                                    format!(" | 获得: {}", rendered.join(","))
-                                }
+                                    .collect::<Vec<_>>()
                             })
                             .unwrap_or_default();
 
-                        logs.push(format!(
-                            "[{}] {}: {} - {}{}",
-                            character.app_code.to_uppercase(),
-                            character.display_name,
+                        SignRecord {
+                            app_code: character.app_code.clone(),
+                            display_name: character.display_name.clone(),
                             status,
-                            msg,
-                            awards
-                        ));
+                            message: msg.to_string(),
+                            awards,
+                            challenge: None,
+                        }
                     }
                     Err(error) => {
-                        all_success = false;
-                        logs.push(format!(
-                            "[{}] {}: ERROR - {}",
-                            character.app_code.to_uppercase(),
-                            character.display_name,
-                            error
-                        ));
+                        let challenge = error
+                            .downcast_ref::<ChallengeError>()
+                            .map(|ChallengeError(challenge)| challenge.clone());
+                        SignRecord {
+                            app_code: character.app_code.clone(),
+                            display_name: character.display_name.clone(),
+                            status: if challenge.is_some() {
+                                SignStatus::Challenge
+                            } else {
+                                SignStatus::Error
+                            },
+                            message: error.to_string(),
+                            awards: Vec::new(),
+                            challenge,
+                        }
                     }
-                }
+                };
+                report.push_record(record);
             }
             Ok(())
         })();
 
-        if let Err(error) = result {
-            all_success = false;
-            logs.push(format!("Login/Init Error: {error}"));
+        for event in self.take_events() {
+            report.push_event(event);
+        }
+        match result {
+            Ok(()) => report.token_health = Some(TokenHealth::Valid),
+            Err(error) => {
+                report.token_health = error.downcast_ref::<TokenError>().map(|error| error.health);
+                report.challenge = error
+                    .downcast_ref::<ChallengeError>()
+                    .map(|ChallengeError(challenge)| challenge.clone());
+                report.fail(format!("Login/Init Error: {error}"));
+            }
         }
+    }
 
-        (all_success, logs)
+    /// Runs `call`; when it is stopped by a captcha or risk-control check,
+    /// hands that to `CHALLENGE_HOOK` and, if the hook dealt with it, runs
+    /// `call` once more with the hook's solution applied.
+    fn with_challenge_hook<T>(
+        &mut self,
+        account: &str,
+        mut call: impl FnMut(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        let error = match call(self) {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+        let solution = error
+            .downcast_ref::<ChallengeError>()
+            .and_then(|ChallengeError(challenge)| challenge::hand_off(challenge, account));
+        let Some(solution) = solution else {
+            return Err(error);
+        };
+        self.solution = Some(solution);
+        let result = call(self);
+        self.solution = None;
+        result
     }
 
-    fn login(&mut self) -> Result<()> {
+    /// Notable things that happened behind the caller's back since the last
+    /// call, such as cred refreshes.
+    pub fn take_events(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Asks Hypergryph for a grant code without going on to Skyland, which is
+    /// enough to tell whether the token still works.
+    pub(crate) fn check_token(&self) -> Result<(TokenHealth, String)> {
+        if self.token.is_none() {
+            return match self.validate_cred() {
+                Ok(()) => Ok((TokenHealth::Valid, "OK".to_string())),
+                Err(error) => match error.downcast::<TokenError>() {
+                    Ok(error) => Ok((error.health, error.message)),
+                    Err(error) => Err(error),
+                },
+            };
+        }
+
+        let grant_response = self.request_grant()?;
+        let message = grant_response
+            .get("msg")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        Ok((TokenHealth::classify(&grant_response), message))
+    }
+
+    fn request_grant(&self) -> Result<Value> {
+        let token = self.token.as_deref().context("missing Hypergryph token")?;
         let grant_body = object_from_pairs([
             ("appCode", Value::String(APP_CODE.to_string())),
-            ("token", Value::String(self.token.clone())),
+            ("token", Value::String(token.to_string())),
             ("type", Value::Number(0.into())),
         ]);
-        let grant_response = self
-            .http
-            .post(GRANT_CODE_URL)
-            .headers(self.base_headers()?)
-            .header("Content-Type", "application/json")
-            .body(python_json_string(&grant_body))
-            .send()
-            .context("failed to request OAuth grant")?
-            .json::<Value>()
-            .context("invalid OAuth grant response")?;
-
-        if grant_response.get("status").and_then(Value::as_i64) != Some(0) {
+        send_json(
+            self.http
+                .post(GRANT_CODE_URL)
+                .headers(self.base_headers()?)
+                .header("Content-Type", "application/json")
+                .body(self.json_body(grant_body)),
+            "OAuth grant",
+        )
+    }
+
+    /// A cheap signed call that only succeeds with a live cred.
+    fn validate_cred(&self) -> Result<()> {
+        let response =
+            self.send_signed_once("get", USER_CHECK_URL, None, HeaderMap::new(), "user check")?;
+
+        let code = response.get("code").and_then(Value::as_i64);
+        if code == Some(0) {
+            return Ok(());
+        }
+
+        let message = response
+            .get("message")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown error")
+            .to_string();
+        let health = if code == Some(CRED_INVALID_CODE) {
+            TokenHealth::Expired
+        } else {
+            TokenHealth::Unknown
+        };
+        Err(TokenError { health, message }.into())
+    }
+
+    fn login(&mut self) -> Result<()> {
+        if self.token.is_none() {
+            return self.validate_cred();
+        }
+
+        let grant_response = self.request_grant()?;
+
+        let health = TokenHealth::classify(&grant_response);
+        if health != TokenHealth::Valid {
             let message = grant_response
                 .get("msg")
                 .and_then(Value::as_str)
                 .unwrap_or("unknown error");
-            return Err(anyhow!("OAuth Grant failed: {message}"));
+            return Err(TokenError {
+                health,
+                message: message.to_string(),
+            }
+            .into());
         }
 
         let grant_code = grant_response
@@ -198,16 +588,15 @@ impl SkylandClient {
             ("code", Value::String(grant_code.to_string())),
             ("kind", Value::Number(1.into())),
         ]);
-        let cred_response = self
-            .http
-            .post(CRED_CODE_URL)
-            .headers(self.base_headers()?)
-            .header("Content-Type", "application/json")
-            .body(python_json_string(&cred_body))
-            .send()
-            .context("failed to request cred")?
-            .json::<Value>()
-            .context("invalid cred response")?;
+        let (cred_response, date) = send_json_dated(
+            self.http
+                .post(CRED_CODE_URL)
+                .headers(self.base_headers()?)
+                .header("Content-Type", "application/json")
+                .body(self.json_body(cred_body)),
+            "cred",
+        )?;
+        self.sync_server_time(date);
 
         if cred_response.get("code").and_then(Value::as_i64) != Some(0) {
             let message = cred_response
@@ -230,19 +619,15 @@ impl SkylandClient {
             .get("token")
             .and_then(Value::as_str)
             .map(ToString::to_string);
+        for secret in self.cred.iter().chain(self.cred_token.iter()) {
+            register(secret);
+        }
 
         Ok(())
     }
 
-    fn get_bindings(&self) -> Result<Vec<Character>> {
-        let response = self
-            .http
-            .get(BINDING_URL)
-            .headers(self.sign_headers(BINDING_URL, "get", None)?)
-            .send()
-            .context("failed to request bindings")?
-            .json::<Value>()
-            .context("invalid bindings response")?;
+    fn get_bindings(&mut self) -> Result<Vec<Character>> {
+        let response = self.send_signed("get", BINDING_URL, None, HeaderMap::new(), "bindings")?;
 
         if response.get("code").and_then(Value::as_i64) != Some(0) {
             let message = response
@@ -328,7 +713,7 @@ impl SkylandClient {
         Ok(bindings)
     }
 
-    fn sign_arknights(&self, character: &Character) -> Result<Value> {
+    fn sign_arknights(&mut self, character: &Character) -> Result<Value> {
         let body = object_from_pairs([
             (
                 "gameId",
@@ -340,21 +725,19 @@ impl SkylandClient {
             ),
         ]);
         let body_string = python_json_string(&body);
-        let response = self
-            .http
-            .post(ARKNIGHTS_SIGN_URL)
-            .headers(self.sign_headers(ARKNIGHTS_SIGN_URL, "post", Some(&body_string))?)
-            .header("Content-Type", "application/json")
-            .body(body_string)
-            .send()
-            .context("failed to request Arknights sign")?
-            .json::<Value>()
-            .context("invalid Arknights sign response")?;
-        Ok(response)
+        let mut headers = HeaderMap::new();
+        headers.insert("Content-Type", HeaderValue::from_static("application/json"));
+        self.send_signed(
+            "post",
+            ARKNIGHTS_SIGN_URL,
+            Some(&body_string),
+            headers,
+            "Arknights sign",
+        )
     }
 
-    fn sign_endfield(&self, character: &Character) -> Result<Value> {
-        let mut headers = self.sign_headers(ENDFIELD_SIGN_URL, "post", Some(""))?;
+    fn sign_endfield(&mut self, character: &Character) -> Result<Value> {
+        let mut headers = HeaderMap::new();
         let role = format!(
             "3_{}_{}",
             character.role_id.clone().context("missing roleId")?,
@@ -366,33 +749,209 @@ impl SkylandClient {
             HeaderValue::from_str(&role).context("invalid sk-game-role header")?,
         );
 
-        let response = self
-            .http
-            .post(ENDFIELD_SIGN_URL)
-            .headers(headers)
-            .send()
-            .context("failed to request Endfield sign")?
-            .json::<Value>()
-            .context("invalid Endfield sign response")?;
+        self.send_signed(
+            "post",
+            ENDFIELD_SIGN_URL,
+            Some(""),
+            headers,
+            "Endfield sign",
+        )
+    }
+
+    /// Calls any `zonai.skland.com` API with the account's cred, logging in
+    /// first if there is none yet. `query` is URL-encoded onto `path`, and
+    /// `body` is sent as JSON; signing, headers, cred refresh and challenges,
+    /// including `CHALLENGE_HOOK`, work as for the built-in calls. Returns
+    /// `data` when Skyland answers `code: 0` and a `SkylandError` otherwise.
+    /// Cred refreshes along the way are left in `take_events`.
+    pub fn request(
+        &mut self,
+        method: &str,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Option<&Value>,
+    ) -> Result<Value> {
+        let label = self.label.clone();
+        if self.cred.is_none() {
+            self.with_challenge_hook(&label, Self::login)?;
+        }
+        let url = skyland_url(path, query)?;
+        let body = body.map(python_json_string);
+        let mut headers = HeaderMap::new();
+        if body.is_some() {
+            headers.insert("Content-Type", HeaderValue::from_static("application/json"));
+        }
+
+        let response = self.with_challenge_hook(&label, |client| {
+            client.send_signed(method, url.as_str(), body.as_deref(), headers.clone(), path)
+        })?;
+        let code = response
+            .get("code")
+            .and_then(Value::as_i64)
+            .context("response has no code")?;
+        if code != 0 {
+            let message = response
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown error");
+            return Err(SkylandError {
+                path: path.to_string(),
+                code,
+                message: message.to_string(),
+            }
+            .into());
+        }
+        Ok(response.get("data").cloned().unwrap_or(Value::Null))
+    }
+
+    /// Sends a request signed with the current cred. When Skyland answers
+    /// that the cred is no longer logged in and a Hypergryph token is at
+    /// hand, logs in again and retries once with the fresh cred.
+    fn send_signed(
+        &mut self,
+        method: &str,
+        url: &str,
+        body: Option<&str>,
+        extra_headers: HeaderMap,
+        action: &str,
+    ) -> Result<Value> {
+        let response = self.send_signed_once(method, url, body, extra_headers.clone(), action)?;
+        let code = response.get("code").and_then(Value::as_i64);
+        let Some(code) = code.filter(|code| *code == CRED_INVALID_CODE) else {
+            return Ok(response);
+        };
+        if self.token.is_none() {
+            return Ok(response);
+        }
+
+        let message = response
+            .get("message")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown error")
+            .to_string();
+        self.login()
+            .inspect_err(|_| metrics::record_login_failure())
+            .with_context(|| format!("failed to refresh cred after {action} returned {code}"))?;
+        self.events.push(format!(
+            "Cred refreshed during {action} ({code}: {message})"
+        ));
+        self.send_signed_once(method, url, body, extra_headers, action)
+    }
+
+    /// Sends a signed request, keeping the server time offset up to date
+    /// from the answer. A timestamp complaint that came with a different
+    /// server time is retried once with the corrected offset.
+    fn send_signed_once(
+        &self,
+        method: &str,
+        url: &str,
+        body: Option<&str>,
+        extra_headers: HeaderMap,
+        action: &str,
+    ) -> Result<Value> {
+        let (response, date) =
+            self.send_signed_request(method, url, body, extra_headers.clone(), action)?;
+        if !self.sync_server_time(date) || !is_timestamp_error(&response) {
+            return Ok(response);
+        }
+        let (response, date) =
+            self.send_signed_request(method, url, body, extra_headers, action)?;
+        self.sync_server_time(date);
         Ok(response)
     }
 
+    fn send_signed_request(
+        &self,
+        method: &str,
+        url: &str,
+        body: Option<&str>,
+        extra_headers: HeaderMap,
+        action: &str,
+    ) -> Result<(Value, Option<DateTime<FixedOffset>>)> {
+        let solved = body.and_then(|body| self.solved_body(body));
+        let body = solved.as_deref().or(body);
+        let mut headers = self.sign_headers(url, method, body)?;
+        headers.extend(extra_headers);
+
+        let method = Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+            .with_context(|| format!("invalid HTTP method {method:?}"))?;
+        let request = self.http.request(method, url).headers(headers);
+        let request = match body.filter(|body| !body.is_empty()) {
+            Some(body) => request.body(body.to_string()),
+            None => request,
+        };
+
+        send_json_dated(request, action)
+    }
+
+    /// Adopts the offset implied by a Skyland `Date` header when it moved by
+    /// more than header jitter; returns whether it did.
+    fn sync_server_time(&self, date: Option<DateTime<FixedOffset>>) -> bool {
+        let Some(date) = date else {
+            return false;
+        };
+        let previous = self.server_offset_ms.get();
+        let Some(offset) = moved_offset(previous, server_offset_ms(self.clock.as_ref(), date))
+        else {
+            return false;
+        };
+        self.server_offset_ms.set(offset);
+        let seconds = offset as f64 / 1000.0;
+        if previous == 0 {
+            info!("Server clock is {seconds:+.1}s from ours");
+        } else {
+            debug!("Server clock is now {seconds:+.1}s from ours");
+        }
+        true
+    }
+
     fn base_headers(&self) -> Result<HeaderMap> {
         let mut headers = HeaderMap::new();
-        headers.insert("User-Agent", HeaderValue::from_static(USER_AGENT));
+        headers.insert(
+            "User-Agent",
+            HeaderValue::from_static(self.platform.user_agent()),
+        );
         headers.insert("Accept-Encoding", HeaderValue::from_static("gzip"));
         headers.insert("Connection", HeaderValue::from_static("close"));
         headers.insert(
             "X-Requested-With",
-            HeaderValue::from_static("com.hypergryph.skland"),
+            HeaderValue::from_static(self.platform.requested_with()),
         );
         headers.insert(
             "dId",
             HeaderValue::from_str(&self.d_id).context("invalid dId header")?,
         );
+        for (name, value) in self.solution.iter().flat_map(|solution| &solution.headers) {
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes())
+                    .with_context(|| format!("invalid challenge header {name}"))?,
+                HeaderValue::from_str(value)
+                    .with_context(|| format!("invalid challenge header {name}"))?,
+            );
+        }
         Ok(headers)
     }
 
+    /// `body` as sent, with any challenge solution fields merged in.
+    fn json_body(&self, mut body: Value) -> String {
+        if let (Some(solution), Value::Object(map)) = (&self.solution, &mut body) {
+            map.extend(solution.body.clone());
+        }
+        python_json_string(&body)
+    }
+
+    /// A serialised JSON body with the challenge solution merged in, or
+    /// `None` when there is nothing to merge into.
+    fn solved_body(&self, body: &str) -> Option<String> {
+        if self.solution.as_ref()?.body.is_empty() {
+            return None;
+        }
+        match serde_json::from_str::<Value>(body) {
+            Ok(body @ Value::Object(_)) => Some(self.json_body(body)),
+            _ => None,
+        }
+    }
+
     fn sign_headers(&self, url: &str, method: &str, body: Option<&str>) -> Result<HeaderMap> {
         let mut headers = self.base_headers()?;
         let cred = self.cred.as_deref().context("missing cred")?;
@@ -412,41 +971,106 @@ impl SkylandClient {
             "sign",
             HeaderValue::from_str(&signature.sign).context("invalid sign header")?,
         );
-        headers.insert("platform", HeaderValue::from_static("3"));
+        headers.insert("platform", HeaderValue::from_static(self.platform.code()));
         headers.insert(
             "timestamp",
             HeaderValue::from_str(&signature.timestamp).context("invalid timestamp header")?,
         );
-        headers.insert("vName", HeaderValue::from_static("1.0.0"));
+        headers.insert("vName", HeaderValue::from_static(self.platform.v_name()));
         Ok(headers)
     }
 
     fn generate_signature(&self, path: &str, body_or_query: &str) -> Result<SignatureHeaders> {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .context("system clock is before unix epoch")?
-            .as_secs()
-            .saturating_sub(2)
-            .to_string();
-
         let cred_token = self.cred_token.as_deref().context("missing cred token")?;
-        let header_ca = format!(
-            "{{\"platform\":\"3\",\"timestamp\":\"{}\",\"dId\":{},\"vName\":\"1.0.0\"}}",
-            timestamp,
-            serde_json::to_string(&self.d_id).context("failed to encode dId")?
-        );
-        let signing_string = format!("{path}{body_or_query}{timestamp}{header_ca}");
+        generate_signature(
+            &OffsetClock(self.clock.as_ref(), self.server_offset_ms.get()),
+            self.pacing
+                .timestamp_offset(self.random.borrow_mut().as_mut()),
+            &self.platform,
+            cred_token,
+            &self.d_id,
+            path,
+            body_or_query,
+        )
+    }
+}
 
-        let mut mac =
-            HmacSha256::new_from_slice(cred_token.as_bytes()).context("invalid HMAC key")?;
-        mac.update(signing_string.as_bytes());
-        let hmac_hex = hex::encode(mac.finalize().into_bytes());
+/// The `sign` header: MD5 of the HMAC-SHA256 (keyed with the cred token) of
+/// path, body or query, timestamp and the header fields it covers. The
+/// timestamp lags the clock by `offset_secs`.
+fn generate_signature(
+    clock: &dyn Clock,
+    offset_secs: i64,
+    platform: &Platform,
+    cred_token: &str,
+    d_id: &str,
+    path: &str,
+    body_or_query: &str,
+) -> Result<SignatureHeaders> {
+    let timestamp = clock.unix_seconds().saturating_sub(offset_secs).to_string();
 
-        Ok(SignatureHeaders {
-            sign: md5_hex(hmac_hex),
-            timestamp,
-        })
+    let header_ca = format!(
+        "{{\"platform\":\"{}\",\"timestamp\":\"{}\",\"dId\":{},\"vName\":\"{}\"}}",
+        platform.code(),
+        timestamp,
+        serde_json::to_string(&d_id).context("failed to encode dId")?,
+        platform.v_name()
+    );
+    let signing_string = format!("{path}{body_or_query}{timestamp}{header_ca}");
+
+    let mut mac = HmacSha256::new_from_slice(cred_token.as_bytes()).context("invalid HMAC key")?;
+    mac.update(signing_string.as_bytes());
+    let hmac_hex = hex::encode(mac.finalize().into_bytes());
+
+    Ok(SignatureHeaders {
+        sign: md5_hex(hmac_hex),
+        timestamp,
+    })
+}
+
+/// Joins `path` onto the Skyland API host, refusing anything that would
+/// send the cred elsewhere.
+fn skyland_url(path: &str, query: &[(&str, &str)]) -> Result<Url> {
+    if !path.starts_with('/') || path.starts_with("//") {
+        bail!("Skyland API path must start with a single /, got {path:?}");
+    }
+    let mut url = Url::parse(&format!("{SKYLAND_BASE_URL}{path}"))
+        .with_context(|| format!("invalid Skyland API path {path:?}"))?;
+    if !query.is_empty() {
+        url.query_pairs_mut().extend_pairs(query);
     }
+    Ok(url)
+}
+
+/// How far a server's clock is ahead of `clock`, judging by a `Date` header
+/// just received. The header is truncated to the second, so half a second
+/// is added back, and anything under `MIN_SERVER_OFFSET_MS` counts as none.
+fn server_offset_ms(clock: &dyn Clock, date: DateTime<FixedOffset>) -> i64 {
+    let offset = date.timestamp_millis() + 500 - clock.unix_millis();
+    if offset.abs() < MIN_SERVER_OFFSET_MS {
+        0
+    } else {
+        offset
+    }
+}
+
+/// `measured` if it differs from `current` by at least
+/// `MIN_SERVER_OFFSET_MS`; every `Date` header gives a slightly different
+/// offset, which is not worth a change.
+fn moved_offset(current: i64, measured: i64) -> Option<i64> {
+    ((measured - current).abs() >= MIN_SERVER_OFFSET_MS).then_some(measured)
+}
+
+fn is_timestamp_error(response: &Value) -> bool {
+    response.get("code").and_then(Value::as_i64) == Some(TIMESTAMP_ERROR_CODE)
+}
+
+fn token_from_login_data(data: &Value) -> Result<String> {
+    data.get("token")
+        .and_then(Value::as_str)
+        .filter(|token| !token.is_empty())
+        .map(ToString::to_string)
+        .context("missing token in login response")
 }
 
 fn render_plain_value(value: &Value) -> String {
@@ -456,3 +1080,299 @@ fn render_plain_value(value: &Value) -> String {
         other => other.to_string(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::system::fixed::{CountingRandom, FixedClock};
+
+    /// Answers one HTTP request per entry of `responses`, each a JSON body
+    /// with an optional `Date` header, and returns the requests it got.
+    fn serve(
+        responses: Vec<(Option<&'static str>, &'static str)>,
+    ) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!(
+            // Not a real endpoint, so the shared latency metrics other tests
+            // assert on stay untouched.
+            "http://{}/api/v1/test/signed",
+            listener.local_addr().unwrap()
+        );
+        let handle = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for (date, body) in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                let mut length = 0;
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    request.push_str(&line);
+                    line.clear();
+                }
+                let mut request_body = vec![0; length];
+                reader.read_exact(&mut request_body).unwrap();
+                request.push_str(&String::from_utf8(request_body).unwrap());
+                requests.push(request);
+                let date = date
+                    .map(|date| format!("Date: {date}\r\n"))
+                    .unwrap_or_default();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n{date}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                (&stream).write_all(response.as_bytes()).unwrap();
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    /// A logged-in Android client, so no device profile has to be fetched.
+    fn logged_in_client() -> SkylandClient {
+        let platform = Platform::Android {
+            android_id: "de9759a5afaa634f".to_string(),
+        };
+        let mut client = SkylandClient::from_credential_with(
+            &Credential::Token("hypergryph-token-0123".to_string()),
+            platform,
+            &ShumeiSdk::default(),
+            &DeviceProfile::default(),
+            Box::new(FixedClock(1_767_294_245_678)),
+            Box::new(CountingRandom(0)),
+        )
+        .unwrap();
+        client.cred = Some("cred-0123456789".to_string());
+        client.cred_token = Some("0123456789abcdef0123456789abcdef".to_string());
+        client
+    }
+
+    #[test]
+    fn signature_matches_golden_vector() {
+        let signature = generate_signature(
+            &FixedClock(1_767_294_245_678),
+            2,
+            &Platform::Web,
+            "0123456789abcdef0123456789abcdef",
+            "B1234567890abcdef",
+            "/api/v1/game/attendance",
+            r#"{"gameId": "1", "uid": "12345678"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(signature.timestamp, "1767294243");
+        assert_eq!(signature.sign, "296b2fc897d08555b39bf7b24e47538e");
+    }
+
+    #[test]
+    fn android_signature_covers_app_platform_and_version() {
+        let platform = Platform::Android {
+            android_id: "de9759a5afaa634f".to_string(),
+        };
+        let signature = generate_signature(
+            &FixedClock(1_767_294_245_678),
+            2,
+            &platform,
+            "0123456789abcdef0123456789abcdef",
+            "de9759a5afaa634f",
+            "/api/v1/game/player/binding",
+            "",
+        )
+        .unwrap();
+
+        assert_eq!(signature.sign, "ba73aa2684993143be0e1e1b2f3e9e3d");
+    }
+
+    #[test]
+    fn server_offset_ignores_sub_second_noise() {
+        let clock = FixedClock(1_767_294_245_678);
+        let date = |header: &str| DateTime::parse_from_rfc2822(header).unwrap();
+
+        // 2026-01-01 19:04:05 GMT is the clock's own second.
+        assert_eq!(
+            server_offset_ms(&clock, date("Thu, 01 Jan 2026 19:04:05 GMT")),
+            0
+        );
+        assert_eq!(
+            server_offset_ms(&clock, date("Thu, 01 Jan 2026 19:05:05 GMT")),
+            59_822
+        );
+
+        let shifted = OffsetClock(&clock, 59_822);
+        let signature = generate_signature(
+            &shifted,
+            2,
+            &Platform::Web,
+            "0123456789abcdef0123456789abcdef",
+            "B1234567890abcdef",
+            "/api/v1/game/attendance",
+            "",
+        )
+        .unwrap();
+        assert_eq!(signature.timestamp, "1767294303");
+
+        // Later headers jitter around the adopted offset without moving it.
+        assert_eq!(moved_offset(0, 59_822), Some(59_822));
+        assert_eq!(moved_offset(59_822, 60_321), None);
+        assert_eq!(moved_offset(59_822, 58_400), None);
+        assert_eq!(moved_offset(59_822, 62_000), Some(62_000));
+        assert_eq!(moved_offset(59_822, 0), Some(0));
+    }
+
+    #[test]
+    fn classifies_grant_responses() {
+        let classify = |response| TokenHealth::classify(&response);
+        assert_eq!(
+            classify(json!({"status": 0, "msg": "OK", "data": {"code": "abc"}})),
+            TokenHealth::Valid
+        );
+        assert_eq!(
+            classify(json!({"status": 1, "msg": "登录已过期，请重新登录"})),
+            TokenHealth::Expired
+        );
+        assert_eq!(
+            classify(json!({"status": 3, "msg": "Invalid Token"})),
+            TokenHealth::Expired
+        );
+        assert_eq!(
+            classify(json!({"status": 2, "msg": "账号已被封禁"})),
+            TokenHealth::Banned
+        );
+        assert_eq!(
+            classify(json!({"status": 500, "msg": "服务繁忙"})),
+            TokenHealth::Unknown
+        );
+        assert_eq!(classify(json!({})), TokenHealth::Unknown);
+    }
+
+    #[test]
+    fn request_urls_stay_on_skyland() {
+        let url = skyland_url(
+            "/api/v1/game/player/info",
+            &[("uid", "123"), ("name", "阿米娅 & co")],
+        )
+        .unwrap();
+        assert_eq!(url.host_str(), Some("zonai.skland.com"));
+        assert_eq!(url.path(), "/api/v1/game/player/info");
+        assert_eq!(
+            url.query(),
+            Some("uid=123&name=%E9%98%BF%E7%B1%B3%E5%A8%85+%26+co")
+        );
+        assert_eq!(skyland_url("/api/v1/user/me", &[]).unwrap().query(), None);
+
+        assert!(skyland_url("//example.com/steal", &[]).is_err());
+        assert!(skyland_url("api/v1/user/me", &[]).is_err());
+    }
+
+    #[test]
+    fn already_signed_keeps_the_cred() {
+        let (url, server) = serve(vec![(
+            None,
+            r#"{"code": 10001, "message": "请勿重复签到！", "data": {}}"#,
+        )]);
+        let mut client = logged_in_client();
+
+        let response = client
+            .send_signed("post", &url, Some("{}"), HeaderMap::new(), "Arknights sign")
+            .unwrap();
+        server.join().unwrap();
+
+        assert_eq!(response["code"], 10001);
+        assert_eq!(client.cred.as_deref(), Some("cred-0123456789"));
+        assert!(client.events.is_empty(), "{:?}", client.events);
+    }
+
+    #[test]
+    fn injected_sources_pin_the_sign_headers() {
+        let client = logged_in_client();
+        let headers = client.sign_headers(BINDING_URL, "get", None).unwrap();
+
+        // `CountingRandom(0)` samples a 1 s timestamp lag from the default
+        // pacing.
+        let expected = generate_signature(
+            &FixedClock(1_767_294_245_678),
+            1,
+            &client.platform,
+            "0123456789abcdef0123456789abcdef",
+            "de9759a5afaa634f",
+            "/api/v1/game/player/binding",
+            "",
+        )
+        .unwrap();
+        assert_eq!(headers["timestamp"], "1767294244");
+        assert_eq!(headers["sign"], expected.sign.as_str());
+    }
+
+    #[test]
+    fn retries_carry_the_hook_solution() {
+        let (url, server) = serve(vec![(None, r#"{"code": 0, "message": "OK", "data": {}}"#)]);
+        let mut client = logged_in_client();
+        client.solution = Some(Solution {
+            headers: vec![("X-Captcha-Token".to_string(), "tok".to_string())],
+            body: serde_json::Map::from_iter([("seccode".to_string(), json!("abc|jordan"))]),
+        });
+
+        client
+            .send_signed_once(
+                "post",
+                &url,
+                Some(r#"{"gameId": "1"}"#),
+                HeaderMap::new(),
+                "sign",
+            )
+            .unwrap();
+        let requests = server.join().unwrap();
+
+        let request = requests[0].to_ascii_lowercase();
+        assert!(request.contains("x-captcha-token: tok\r\n"), "{request}");
+        assert!(
+            requests[0].ends_with(r#"{"gameId": "1", "seccode": "abc|jordan"}"#),
+            "{}",
+            requests[0]
+        );
+    }
+
+    #[test]
+    fn stale_timestamp_retries_once_with_the_server_clock() {
+        // A minute ahead of `FixedClock(1_767_294_245_678)`.
+        let date = Some("Thu, 01 Jan 2026 19:05:05 GMT");
+        let (url, server) = serve(vec![
+            (date, r#"{"code": 10003, "message": "请求已过期"}"#),
+            (date, r#"{"code": 0, "message": "OK", "data": {}}"#),
+        ]);
+        let client = logged_in_client();
+
+        let response = client
+            .send_signed_once("get", &url, None, HeaderMap::new(), "user check")
+            .unwrap();
+        let requests = server.join().unwrap();
+
+        assert_eq!(response["code"], 0);
+        assert_eq!(client.server_offset_ms.get(), 59_822);
+        let timestamps = requests
+            .iter()
+            .map(|request| {
+                request
+                    .lines()
+                    .find_map(|line| line.strip_prefix("timestamp: "))
+                    .unwrap()
+                    .parse::<i64>()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(timestamps.len(), 2);
+        assert!(
+            (58..=62).contains(&(timestamps[1] - timestamps[0])),
+            "{timestamps:?}"
+        );
+    }
+}
@@ -0,0 +1,503 @@
+// Copyright 2026 YuzakiKokuban
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::redact::register;
+use crate::secrets::{is_secret_source, resolve_secret, secret_env};
+use crate::template::Template;
+use crate::utils::env_flag;
+use crate::vault::load_vault_accounts;
+
+#[derive(Clone, Debug)]
+pub enum Credential {
+    /// Hypergryph account token, exchanged for a Skyland cred on login.
+    Token(String),
+    /// Skyland `cred` and its signing token, used as-is.
+    Cred { cred: String, token: String },
+}
+
+#[derive(Clone, Debug)]
+pub struct Account {
+    pub label: String,
+    pub credential: Credential,
+}
+
+/// Registers every part of `credential` for redaction.
+pub fn register_credential(credential: &Credential) {
+    match credential {
+        Credential::Token(token) => register(token),
+        Credential::Cred { cred, token } => {
+            register(cred);
+            register(token);
+        }
+    }
+}
+
+impl Credential {
+    /// The part that identifies the account in masked log lines.
+    pub fn secret(&self) -> &str {
+        match self {
+            Self::Token(token) => token,
+            Self::Cred { cred, .. } => cred,
+        }
+    }
+
+    /// What expiry alerts call this credential.
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            Self::Token(_) => "鹰角通行证 Token",
+            Self::Cred { .. } => "森空岛 cred",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotifyFilter {
+    Always,
+    Failure,
+    TokenExpired,
+    Changed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageFormat {
+    Text,
+    Markdown,
+    /// Built-in rich rendering per platform; plain text for generic hooks.
+    Card,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SmtpTls {
+    /// Plain connection upgraded with STARTTLS (submission port 587).
+    StartTls,
+    /// TLS from the first byte (submissions port 465).
+    Implicit,
+    /// No encryption at all; only meant for local relays and test sinks.
+    None,
+}
+
+#[derive(Clone, Debug)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub enum NotifyChannel {
+    Webhook { url: String, secret: Option<String> },
+    Smtp(SmtpConfig),
+}
+
+#[derive(Clone, Debug)]
+pub struct NotifyTarget {
+    pub name: String,
+    pub channel: NotifyChannel,
+    pub filter: NotifyFilter,
+    pub accounts: Vec<String>,
+    pub format: MessageFormat,
+    /// @-mention everyone when sending token expiry alerts.
+    pub mention_all: bool,
+    /// Custom title and body templates; `None` falls back to the built-in
+    /// ones for `format`.
+    pub title: Option<Template>,
+    pub template: Option<Template>,
+}
+
+/// Templates from `NOTIFY_TITLE`/`NOTIFY_TEMPLATE(_FILE)`, used by every
+/// target that does not bring its own.
+struct TemplateDefaults {
+    title: Option<Template>,
+    template: Option<Template>,
+}
+
+impl SmtpTls {
+    fn parse(value: &str) -> Result<Self> {
+        Ok(match value.trim().to_ascii_lowercase().as_str() {
+            "" | "starttls" => Self::StartTls,
+            "implicit" | "tls" | "ssl" => Self::Implicit,
+            "none" | "plain" => Self::None,
+            other => bail!("unknown SMTP tls mode: {other}"),
+        })
+    }
+
+    fn default_port(self) -> u16 {
+        match self {
+            Self::StartTls => 587,
+            Self::Implicit => 465,
+            Self::None => 25,
+        }
+    }
+}
+
+impl MessageFormat {
+    fn parse(value: &str) -> Result<Self> {
+        Ok(match value.trim().to_ascii_lowercase().as_str() {
+            "" | "text" | "plain" => Self::Text,
+            "markdown" | "md" => Self::Markdown,
+            "card" | "rich" => Self::Card,
+            other => bail!("unknown message format: {other}"),
+        })
+    }
+}
+
+impl NotifyFilter {
+    fn parse(value: &str) -> Result<Self> {
+        Ok(match value.trim().to_ascii_lowercase().as_str() {
+            "" | "always" => Self::Always,
+            "failure" | "fail" => Self::Failure,
+            "token_expired" | "expired" => Self::TokenExpired,
+            "changed" => Self::Changed,
+            other => bail!("unknown notify filter: {other}"),
+        })
+    }
+}
+
+/// Each `SKYLAND_TOKEN` entry is either `token` or `label:token`; each
+/// `SKYLAND_CRED` entry is `cred:cred_token` or `label:cred:cred_token`.
+/// Unlabeled accounts are called `Account N`, numbered across both.
+///
+/// Both variables may point at a secret source as a whole; `SKYLAND_TOKEN`
+/// entries may also do so one by one, as `label:file:/path` and the like.
+/// Accounts from the encrypted vault at `VAULT_FILE` come last.
+pub fn load_accounts() -> Result<Vec<Account>> {
+    let mut accounts = Vec::new();
+
+    for entry in secret_entries("SKYLAND_TOKEN")? {
+        let (label, token) = if is_secret_source(&entry) {
+            (None, entry.as_str())
+        } else {
+            match entry.split_once(':') {
+                Some((label, token)) => (Some(label.trim()), token.trim()),
+                None => (None, entry.as_str()),
+            }
+        };
+        let label = account_label(label, accounts.len());
+        let token = resolve_secret(token, &format!("SKYLAND_TOKEN for {label}"))?;
+        if token.is_empty() {
+            continue;
+        }
+        accounts.push(Account {
+            label,
+            credential: Credential::Token(token),
+        });
+    }
+
+    for entry in secret_entries("SKYLAND_CRED")? {
+        let parts = entry.split(':').map(str::trim).collect::<Vec<_>>();
+        let (label, cred, token) = match parts.as_slice() {
+            [cred, token] => (None, *cred, *token),
+            [label, cred, token] => (Some(*label), *cred, *token),
+            _ => bail!("invalid SKYLAND_CRED entry, expected [label:]cred:token"),
+        };
+        if cred.is_empty() || token.is_empty() {
+            bail!("invalid SKYLAND_CRED entry, cred and token must not be empty");
+        }
+        let credential = Credential::Cred {
+            cred: cred.to_string(),
+            token: token.to_string(),
+        };
+        register_credential(&credential);
+        accounts.push(Account {
+            label: account_label(label, accounts.len()),
+            credential,
+        });
+    }
+
+    accounts.extend(load_vault_accounts()?);
+    Ok(accounts)
+}
+
+fn secret_entries(name: &str) -> Result<Vec<String>> {
+    Ok(secret_env(name)?
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(ToString::to_string)
+        .collect())
+}
+
+fn account_label(label: Option<&str>, index: usize) -> String {
+    match label.filter(|label| !label.is_empty()) {
+        Some(label) => label.to_string(),
+        None => format!("Account {}", index + 1),
+    }
+}
+
+/// `WEBHOOK_URL`/`WEBHOOK_SECRET`/`WEBHOOK_FILTER` describe one target for
+/// all accounts; `NOTIFY_TARGETS` adds any number of targets as a JSON array.
+pub fn load_notify_targets() -> Result<Vec<NotifyTarget>> {
+    let defaults = TemplateDefaults {
+        title: non_empty_env("NOTIFY_TITLE")
+            .map(|title| Template::parse(&title))
+            .transpose()
+            .context("invalid NOTIFY_TITLE")?,
+        template: load_template(
+            non_empty_env("NOTIFY_TEMPLATE"),
+            non_empty_env("NOTIFY_TEMPLATE_FILE"),
+        )
+        .context("invalid NOTIFY_TEMPLATE")?,
+    };
+    let mut targets = Vec::new();
+
+    if let Some(url) = secret_env("WEBHOOK_URL")? {
+        targets.push(NotifyTarget {
+            name: "WEBHOOK_URL".to_string(),
+            channel: NotifyChannel::Webhook {
+                url,
+                secret: secret_env("WEBHOOK_SECRET")?,
+            },
+            filter: NotifyFilter::parse(&env::var("WEBHOOK_FILTER").unwrap_or_default())
+                .context("invalid WEBHOOK_FILTER")?,
+            accounts: Vec::new(),
+            format: MessageFormat::parse(&env::var("WEBHOOK_FORMAT").unwrap_or_default())
+                .context("invalid WEBHOOK_FORMAT")?,
+            mention_all: env_flag("WEBHOOK_MENTION_ALL"),
+            title: defaults.title.clone(),
+            template: defaults.template.clone(),
+        });
+    }
+
+    if let Some(raw) = secret_env("NOTIFY_TARGETS")? {
+        let parsed =
+            serde_json::from_str::<Value>(&raw).context("NOTIFY_TARGETS is not valid JSON")?;
+        let entries = parsed
+            .as_array()
+            .context("NOTIFY_TARGETS must be a JSON array")?;
+        for (index, entry) in entries.iter().enumerate() {
+            targets.push(
+                parse_target(index, entry, &defaults)
+                    .with_context(|| format!("invalid NOTIFY_TARGETS entry {}", index + 1))?,
+            );
+        }
+    }
+
+    Ok(targets)
+}
+
+fn parse_target(index: usize, entry: &Value, defaults: &TemplateDefaults) -> Result<NotifyTarget> {
+    let entry = entry.as_object().context("target must be an object")?;
+    let string_field = |key: &str| {
+        entry
+            .get(key)
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(ToString::to_string)
+    };
+
+    let string_list = |key: &str| -> Result<Vec<String>> {
+        match entry.get(key) {
+            None | Some(Value::Null) => Ok(Vec::new()),
+            Some(Value::String(value)) => Ok(value
+                .split(',')
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(ToString::to_string)
+                .collect()),
+            Some(Value::Array(values)) => values
+                .iter()
+                .map(|value| {
+                    value
+                        .as_str()
+                        .map(|value| value.trim().to_string())
+                        .with_context(|| format!("{key} must contain strings"))
+                })
+                .collect(),
+            Some(_) => bail!("{key} must be a string or an array"),
+        }
+    };
+
+    let secret_field = |key: &str| {
+        string_field(key)
+            .map(|value| resolve_secret(&value, &format!("NOTIFY_TARGETS[{}].{key}", index + 1)))
+            .transpose()
+    };
+
+    let kind = string_field("type").unwrap_or_else(|| "webhook".to_string());
+    let channel = match kind.to_ascii_lowercase().as_str() {
+        "webhook" => NotifyChannel::Webhook {
+            url: secret_field("url")?.context("missing url")?,
+            secret: secret_field("secret")?,
+        },
+        "smtp" | "email" => {
+            let tls = SmtpTls::parse(&string_field("tls").unwrap_or_default())?;
+            let port = match entry.get("port") {
+                None | Some(Value::Null) => tls.default_port(),
+                Some(value) => value
+                    .as_u64()
+                    .and_then(|port| u16::try_from(port).ok())
+                    .context("port must be a number between 0 and 65535")?,
+            };
+            let to = string_list("to")?;
+            if to.is_empty() {
+                bail!("missing to");
+            }
+            NotifyChannel::Smtp(SmtpConfig {
+                host: string_field("host").context("missing host")?,
+                port,
+                tls,
+                username: string_field("username"),
+                password: secret_field("password")?,
+                from: string_field("from").context("missing from")?,
+                to,
+            })
+        }
+        other => bail!("unknown target type: {other}"),
+    };
+
+    let accounts = match entry.get("accounts") {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::Array(values)) => values
+            .iter()
+            .map(|value| match value {
+                Value::String(value) => Ok(value.trim().to_string()),
+                Value::Number(value) => Ok(value.to_string()),
+                _ => Err(anyhow!("accounts must contain labels or indexes")),
+            })
+            .collect::<Result<Vec<_>>>()?,
+        Some(_) => bail!("accounts must be an array"),
+    };
+
+    Ok(NotifyTarget {
+        name: string_field("name").unwrap_or_else(|| format!("target {}", index + 1)),
+        channel,
+        filter: NotifyFilter::parse(&string_field("filter").unwrap_or_default())?,
+        accounts,
+        format: MessageFormat::parse(&string_field("format").unwrap_or_default())?,
+        mention_all: entry
+            .get("mention_all")
+            .and_then(Value::as_bool)
+            .unwrap_or(false),
+        title: match string_field("title") {
+            Some(title) => Some(Template::parse(&title).context("invalid title")?),
+            None => defaults.title.clone(),
+        },
+        template: match load_template(string_field("template"), string_field("template_file"))
+            .context("invalid template")?
+        {
+            Some(template) => Some(template),
+            None => defaults.template.clone(),
+        },
+    })
+}
+
+fn load_template(inline: Option<String>, file: Option<String>) -> Result<Option<Template>> {
+    let source = match (inline, file) {
+        (Some(inline), _) => inline,
+        (None, Some(path)) => {
+            fs::read_to_string(&path).with_context(|| format!("failed to read {path}"))?
+        }
+        (None, None) => return Ok(None),
+    };
+    Template::parse(&source).map(Some)
+}
+
+pub fn non_empty_env(name: &str) -> Option<String> {
+    env::var(name)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// A per-account setting such as `DEVICE_PROFILE` or `SKYLAND_PLATFORM`:
+/// comma-separated entries where a bare name sets the default and
+/// `label:name` overrides it for one account.
+///
+/// Some names (`random` profiles, the `android` platform) stand for values
+/// derived from the account label and `DEVICE_PROFILE_SEED`, which is then
+/// required: seeding from the token instead would hand an account a new
+/// device every time the token is rotated.
+pub struct AccountChoice {
+    kind: &'static str,
+    default: String,
+    per_account: BTreeMap<String, String>,
+    seed: Option<String>,
+}
+
+impl AccountChoice {
+    pub fn new(kind: &'static str, default: &str, seed: Option<&str>) -> Self {
+        AccountChoice {
+            kind,
+            default: default.to_string(),
+            per_account: BTreeMap::new(),
+            seed: seed.map(ToString::to_string),
+        }
+    }
+
+    /// Reads `var` and `DEVICE_PROFILE_SEED`; `is_known` accepts the names
+    /// `kind` can take, and `seeded` is the one that needs the seed.
+    pub fn from_env(
+        var: &str,
+        kind: &'static str,
+        default: &str,
+        seeded: &str,
+        is_known: impl Fn(&str) -> bool,
+    ) -> Result<Self> {
+        let seed = non_empty_env("DEVICE_PROFILE_SEED");
+        let mut choice = AccountChoice::new(kind, default, seed.as_deref());
+        for entry in env::var(var).unwrap_or_default().split(',') {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+            let (label, name) = match entry.split_once(':') {
+                Some((label, name)) => (Some(label.trim()), name.trim()),
+                None => (None, entry),
+            };
+            if !is_known(name) {
+                bail!("unknown {kind} {name} in {var}");
+            }
+            match label {
+                Some(label) => {
+                    choice
+                        .per_account
+                        .insert(label.to_string(), name.to_string());
+                }
+                None => choice.default = name.to_string(),
+            }
+        }
+
+        if choice.seed.is_none()
+            && (choice.default == seeded || choice.per_account.values().any(|name| name == seeded))
+        {
+            bail!("the {seeded} {kind} needs DEVICE_PROFILE_SEED");
+        }
+        Ok(choice)
+    }
+
+    pub fn default_name(&self) -> &str {
+        &self.default
+    }
+
+    pub fn name(&self, label: &str) -> &str {
+        self.per_account.get(label).unwrap_or(&self.default)
+    }
+
+    /// SHA-256 of `prefix`, the seed and `label`, the source of everything
+    /// derived per account. `prefix` keeps derivations for different
+    /// settings apart.
+    pub fn seeded_digest(&self, prefix: &str, label: &str) -> Result<[u8; 32]> {
+        let seed = self.seed.as_deref().with_context(|| {
+            format!(
+                "the {} {} needs DEVICE_PROFILE_SEED",
+                self.name(label),
+                self.kind
+            )
+        })?;
+        Ok(Sha256::digest(format!("{prefix}{seed}\n{label}").as_bytes()).into())
+    }
+}
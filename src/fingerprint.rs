@@ -0,0 +1,392 @@
+// Copyright 2026 YuzakiKokuban
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Browser fingerprints reported to Shumei when requesting a `dId`.
+//!
+//! A profile is either a built-in preset, one defined in `DEVICE_PROFILES`
+//! (a JSON object of name to fields, optionally starting from a `base`
+//! preset), or `random`: a preset with browser version, screen, pixel ratio
+//! and canvas hash drawn within realistic bounds. Random profiles are seeded
+//! from the account label and `DEVICE_PROFILE_SEED`, which is required for
+//! them, so an account keeps presenting the same device across runs and
+//! token rotations.
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use anyhow::{Context, Result, bail};
+use rand::rngs::Xoshiro256PlusPlus;
+use rand::seq::IndexedRandom;
+use rand::{RngExt, SeedableRng};
+use serde_json::{Number, Value};
+
+use crate::config::{AccountChoice, non_empty_env};
+use crate::utils::object_from_pairs;
+
+const EDGE_PLUGINS: &str = "MicrosoftEdgePDFPluginPortableDocumentFormatinternal-pdf-viewer1,MicrosoftEdgePDFViewermhjfbmdgcfjbbpaeojofohoefgiehjai1";
+const CHROME_PLUGINS: &str = "PDFViewerPortableDocumentFormatinternal-pdf-viewer1,ChromePDFViewerPortableDocumentFormatinternal-pdf-viewer1,ChromiumPDFViewerPortableDocumentFormatinternal-pdf-viewer1,MicrosoftEdgePDFViewerPortableDocumentFormatinternal-pdf-viewer1,WebKitbuilt-inPDFPortableDocumentFormatinternal-pdf-viewer1";
+
+/// Browser majors that were current while Skyland's web sign-in looked like
+/// it does now; anything far outside stands out.
+const CHROMIUM_VERSIONS: std::ops::RangeInclusive<u32> = 120..=131;
+const FIREFOX_VERSIONS: std::ops::RangeInclusive<u32> = 120..=132;
+/// Common desktop resolutions with the pixel ratios they usually come with.
+const SCREENS: [(u32, u32, &[&str]); 6] = [
+    (1920, 1080, &["1", "1.25", "1.5"]),
+    (2560, 1440, &["1", "1.25", "1.5"]),
+    (1536, 864, &["1.25"]),
+    (1366, 768, &["1"]),
+    (1440, 900, &["2"]),
+    (1680, 1050, &["1", "2"]),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Browser {
+    Edge,
+    Chrome,
+    Firefox,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceProfile {
+    pub name: String,
+    pub plugins: String,
+    pub ua: String,
+    pub canvas: String,
+    pub timezone: i64,
+    pub platform: String,
+    /// `width_height_colorDepth_pixelRatio`.
+    pub res: String,
+    pub client_size: String,
+}
+
+/// How each account picks its profile: `DEVICE_PROFILE` entries are either a
+/// bare profile name (the default) or `label:name`.
+pub struct DeviceProfiles {
+    choice: AccountChoice,
+    custom: BTreeMap<String, DeviceProfile>,
+}
+
+impl Default for DeviceProfile {
+    /// The fingerprint SKAS always used before profiles existed.
+    fn default() -> Self {
+        preset("edge-win").expect("built-in preset")
+    }
+}
+
+impl DeviceProfile {
+    pub fn browser_env(&self) -> Value {
+        object_from_pairs([
+            ("plugins", Value::String(self.plugins.clone())),
+            ("ua", Value::String(self.ua.clone())),
+            ("canvas", Value::String(self.canvas.clone())),
+            ("timezone", Value::Number(Number::from(self.timezone))),
+            ("platform", Value::String(self.platform.clone())),
+            ("url", Value::String("https://www.skland.com/".to_string())),
+            ("referer", Value::String(String::new())),
+            ("res", Value::String(self.res.clone())),
+            ("clientSize", Value::String(self.client_size.clone())),
+            ("status", Value::String("0011".to_string())),
+        ])
+    }
+
+    /// A preset with its variable parts drawn from `rng`.
+    fn random(rng: &mut Xoshiro256PlusPlus) -> Self {
+        let (base, browser, platform) = *[
+            ("edge-win", Browser::Edge, "Win32"),
+            ("chrome-win", Browser::Chrome, "Win32"),
+            ("chrome-mac", Browser::Chrome, "MacIntel"),
+            ("firefox-win", Browser::Firefox, "Win32"),
+        ]
+        .choose(rng)
+        .expect("non-empty presets");
+        let mut profile = preset(base).expect("built-in preset");
+
+        let version = match browser {
+            Browser::Firefox => rng.random_range(FIREFOX_VERSIONS),
+            Browser::Edge | Browser::Chrome => rng.random_range(CHROMIUM_VERSIONS),
+        };
+        profile.ua = user_agent(browser, platform, version);
+
+        let screens = if platform == "MacIntel" {
+            &SCREENS[4..]
+        } else {
+            &SCREENS[..4]
+        };
+        let (width, height, ratios) = *screens.choose(rng).expect("non-empty screens");
+        let ratio = ratios.choose(rng).expect("non-empty ratios");
+        let depth = if platform == "MacIntel" { 30 } else { 24 };
+        profile.res = format!("{width}_{height}_{depth}_{ratio}");
+        profile.client_size = client_size(width, height);
+        profile.canvas = format!("{:08x}", rng.random::<u32>());
+        profile.name = format!("random:{base}");
+        profile
+    }
+}
+
+impl DeviceProfiles {
+    /// The profile for one account; `random` is derived from `label` and the
+    /// seed.
+    pub fn for_account(&self, label: &str) -> Result<DeviceProfile> {
+        let name = self.choice.name(label);
+        if name == "random" {
+            let digest = self.choice.seeded_digest("", label)?;
+            let mut rng = Xoshiro256PlusPlus::from_seed(digest);
+            return Ok(DeviceProfile::random(&mut rng));
+        }
+        self.named(Some(name))
+            .with_context(|| format!("no device profile for {label}"))
+    }
+
+    /// A custom profile or preset by name, or the default when `name` is
+    /// `None`. `random` depends on an account, so it is not accepted here.
+    pub fn named(&self, name: Option<&str>) -> Result<DeviceProfile> {
+        let name = name.unwrap_or(self.choice.default_name());
+        if name == "random" {
+            bail!("the random device profile needs an account");
+        }
+        if let Some(profile) = self.custom.get(name) {
+            return Ok(profile.clone());
+        }
+        preset(name).with_context(|| format!("unknown device profile {name}"))
+    }
+}
+
+/// Reads `DEVICE_PROFILE`, `DEVICE_PROFILE_SEED` and custom profiles from
+/// `DEVICE_PROFILES` (inline JSON) or `DEVICE_PROFILES_FILE`.
+pub fn load_device_profiles() -> Result<DeviceProfiles> {
+    let raw = match (
+        non_empty_env("DEVICE_PROFILES"),
+        non_empty_env("DEVICE_PROFILES_FILE"),
+    ) {
+        (Some(raw), _) => Some(raw),
+        (None, Some(path)) => {
+            Some(fs::read_to_string(&path).with_context(|| format!("failed to read {path}"))?)
+        }
+        (None, None) => None,
+    };
+    let custom = match raw {
+        Some(raw) => parse_custom(&raw)?,
+        None => BTreeMap::new(),
+    };
+
+    let choice = AccountChoice::from_env(
+        "DEVICE_PROFILE",
+        "device profile",
+        "edge-win",
+        "random",
+        |name| name == "random" || custom.contains_key(name) || preset(name).is_some(),
+    )?;
+    Ok(DeviceProfiles { choice, custom })
+}
+
+fn parse_custom(raw: &str) -> Result<BTreeMap<String, DeviceProfile>> {
+    let parsed = serde_json::from_str::<Value>(raw).context("DEVICE_PROFILES is not valid JSON")?;
+    let entries = parsed
+        .as_object()
+        .context("DEVICE_PROFILES must be a JSON object of name to profile")?;
+
+    let mut profiles = BTreeMap::new();
+    for (name, fields) in entries {
+        let profile = parse_profile(name, fields)
+            .with_context(|| format!("invalid device profile {name}"))?;
+        profiles.insert(name.clone(), profile);
+    }
+    Ok(profiles)
+}
+
+fn parse_profile(name: &str, fields: &Value) -> Result<DeviceProfile> {
+    if name == "random" {
+        bail!("random is reserved");
+    }
+    let fields = fields.as_object().context("expected a JSON object")?;
+    let string_field = |key: &str| -> Result<Option<String>> {
+        match fields.get(key) {
+            None => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value.clone())),
+            Some(_) => bail!("{key} must be a string"),
+        }
+    };
+
+    let base = string_field("base")?.unwrap_or_else(|| "edge-win".to_string());
+    let mut profile = preset(&base).with_context(|| format!("unknown base preset {base}"))?;
+    profile.name = name.to_string();
+    if let Some(plugins) = string_field("plugins")? {
+        profile.plugins = plugins;
+    }
+    if let Some(ua) = string_field("ua")? {
+        profile.ua = ua;
+    }
+    if let Some(canvas) = string_field("canvas")? {
+        if canvas.len() != 8 || !canvas.chars().all(|c| c.is_ascii_hexdigit()) {
+            bail!("canvas must be 8 hex digits");
+        }
+        profile.canvas = canvas;
+    }
+    if let Some(timezone) = fields.get("timezone") {
+        profile.timezone = timezone
+            .as_i64()
+            .filter(|minutes| (-840..=720).contains(minutes))
+            .context("timezone must be an offset in minutes between -840 and 720")?;
+    }
+    if let Some(platform) = string_field("platform")? {
+        profile.platform = platform;
+    }
+    if let Some(res) = string_field("res")? {
+        let parts = res.split('_').collect::<Vec<_>>();
+        let [width, height, _, _] = parts.as_slice() else {
+            bail!("res must look like width_height_depth_ratio");
+        };
+        let (Ok(width), Ok(height)) = (width.parse::<u32>(), height.parse::<u32>()) else {
+            bail!("res must start with a numeric width and height");
+        };
+        profile.res = res.clone();
+        profile.client_size = client_size(width, height);
+    }
+    if let Some(client_size) = string_field("clientSize")? {
+        profile.client_size = client_size;
+    }
+    Ok(profile)
+}
+
+/// Built-in presets; `edge-win` is the original hard-coded fingerprint.
+pub fn preset(name: &str) -> Option<DeviceProfile> {
+    let (browser, platform, version, plugins, canvas, width, height, depth, ratio) = match name {
+        "edge-win" => (
+            Browser::Edge,
+            "Win32",
+            129,
+            EDGE_PLUGINS,
+            "259ffe69",
+            1920,
+            1080,
+            24,
+            "1.25",
+        ),
+        "chrome-win" => (
+            Browser::Chrome,
+            "Win32",
+            129,
+            CHROME_PLUGINS,
+            "9a5f3c1e",
+            1920,
+            1080,
+            24,
+            "1",
+        ),
+        "chrome-mac" => (
+            Browser::Chrome,
+            "MacIntel",
+            129,
+            CHROME_PLUGINS,
+            "6c1d8e27",
+            1440,
+            900,
+            30,
+            "2",
+        ),
+        "firefox-win" => (
+            Browser::Firefox,
+            "Win32",
+            130,
+            CHROME_PLUGINS,
+            "c84f02b9",
+            2560,
+            1440,
+            24,
+            "1",
+        ),
+        _ => return None,
+    };
+    Some(DeviceProfile {
+        name: name.to_string(),
+        plugins: plugins.to_string(),
+        ua: user_agent(browser, platform, version),
+        canvas: canvas.to_string(),
+        timezone: -480,
+        platform: platform.to_string(),
+        res: format!("{width}_{height}_{depth}_{ratio}"),
+        client_size: client_size(width, height),
+    })
+}
+
+fn user_agent(browser: Browser, platform: &str, version: u32) -> String {
+    let os = if platform == "MacIntel" {
+        "Macintosh; Intel Mac OS X 10_15_7"
+    } else {
+        "Windows NT 10.0; Win64; x64"
+    };
+    match browser {
+        Browser::Edge => format!(
+            "Mozilla/5.0 ({os}) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/{version}.0.0.0 Safari/537.36 Edg/{version}.0.0.0"
+        ),
+        Browser::Chrome => format!(
+            "Mozilla/5.0 ({os}) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/{version}.0.0.0 Safari/537.36"
+        ),
+        Browser::Firefox => {
+            let os = os.replace("10_15_7", "10.15");
+            format!("Mozilla/5.0 ({os}; rv:{version}.0) Gecko/20100101 Firefox/{version}.0")
+        }
+    }
+}
+
+/// Shumei's `clientSize` for a maximised window on a `width`x`height` screen.
+fn client_size(width: u32, height: u32) -> String {
+    format!("0_0_{height}_{width}_{width}_{height}_{width}_{height}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profiles(default: &str) -> DeviceProfiles {
+        DeviceProfiles {
+            choice: AccountChoice::new("device profile", default, Some("seed")),
+            custom: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn default_profile_matches_original_fingerprint() {
+        let env = DeviceProfile::default().browser_env();
+        assert_eq!(
+            env["ua"],
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/129.0.0.0 Safari/537.36 Edg/129.0.0.0"
+        );
+        assert_eq!(env["res"], "1920_1080_24_1.25");
+        assert_eq!(env["clientSize"], "0_0_1080_1920_1920_1080_1920_1080");
+        assert_eq!(env["canvas"], "259ffe69");
+    }
+
+    #[test]
+    fn random_profile_is_stable_per_account() {
+        let mut profiles = profiles("random");
+        let first = profiles.for_account("alice").unwrap();
+        assert_eq!(first, profiles.for_account("alice").unwrap());
+        assert!(first.name.starts_with("random:"));
+
+        let names = (0..16)
+            .map(|index| profiles.for_account(&format!("account-{index}")).unwrap())
+            .map(|profile| (profile.ua, profile.res, profile.canvas))
+            .collect::<std::collections::BTreeSet<_>>();
+        assert!(names.len() > 1);
+
+        profiles.choice = AccountChoice::new("device profile", "random", None);
+        assert!(profiles.for_account("alice").is_err());
+    }
+
+    #[test]
+    fn custom_profile_overrides_base_preset() {
+        let custom = parse_custom(
+            r#"{"laptop": {"base": "chrome-win", "res": "1366_768_24_1", "canvas": "0badf00d"}}"#,
+        )
+        .unwrap();
+        let laptop = &custom["laptop"];
+        assert_eq!(laptop.client_size, "0_0_768_1366_1366_768_1366_768");
+        assert_eq!(laptop.canvas, "0badf00d");
+        assert!(laptop.ua.contains("Chrome/129"));
+
+        assert!(parse_custom(r#"{"bad": {"timezone": 5000}}"#).is_err());
+        assert!(parse_custom(r#"{"random": {}}"#).is_err());
+    }
+}
@@ -0,0 +1,40 @@
+// Copyright 2026 YuzakiKokuban
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! The Skyland client behind the `skas` binary, for calling Skyland APIs
+//! from other programs: build a `SkylandClient` and call its `request` for
+//! any `zonai.skland.com` endpoint. Everything else is internal to the CLI.
+
+mod cards;
+mod challenge;
+mod cli;
+mod client;
+mod config;
+mod fingerprint;
+mod logging;
+mod metrics;
+mod notifier;
+mod pacing;
+mod platform;
+mod redact;
+mod report;
+mod secrets;
+mod security;
+mod state;
+mod system;
+mod template;
+mod utils;
+mod vault;
+
+pub use challenge::{Challenge, ChallengeError};
+pub use client::{SkylandClient, SkylandError};
+pub use config::Credential;
+pub use fingerprint::DeviceProfile;
+pub use platform::Platform;
+pub use security::ShumeiSdk;
+pub use system::{Clock, RandomSource, SystemClock, SystemRandom};
+
+#[doc(hidden)]
+pub fn cli_main() {
+    cli::main();
+}
@@ -0,0 +1,215 @@
+// Copyright 2026 YuzakiKokuban
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Log setup and HTTP tracing. Diagnostics go through `tracing` to stderr,
+//! every line passing through `redact` first, so stdout only carries what a
+//! command is asked to print (a token, a vault listing, check results).
+//!
+//! `RUST_LOG` takes the usual filter syntax and wins over `--verbose`, which
+//! is short for `RUST_LOG=skas=debug`. `LOG_FORMAT=json` switches to one JSON
+//! object per line.
+
+use std::io::{self, IsTerminal, Write};
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset};
+use reqwest::blocking::RequestBuilder;
+use reqwest::header::DATE;
+use serde_json::Value;
+use tracing::{Level, debug, enabled};
+use tracing_subscriber::EnvFilter;
+
+use crate::challenge::{self, ChallengeError};
+use crate::config::non_empty_env;
+use crate::metrics;
+use crate::redact::{redact, register_fields};
+
+/// Installs the global subscriber; call once, before anything logs.
+pub fn init(verbose: bool) {
+    let default = if verbose { "skas=debug" } else { "skas=info" };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default));
+    let json =
+        non_empty_env("LOG_FORMAT").is_some_and(|format| format.eq_ignore_ascii_case("json"));
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_target(false)
+        .with_writer(|| RedactingWriter(io::stderr()));
+    if json {
+        builder.json().with_current_span(true).init();
+    } else {
+        builder.with_ansi(io::stderr().is_terminal()).init();
+    }
+}
+
+/// The formatter hands over each event as one complete line, so redacting
+/// per `write` call never splits a secret.
+struct RedactingWriter<W>(W);
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        self.0.write_all(redact(&text).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Sends `request` and parses the JSON answer, recording its latency. At
+/// debug level logs method, path, status, latency and both bodies, all
+/// redacted like any other line. Captcha and risk-control answers become a
+/// `ChallengeError`.
+pub fn send_json(request: RequestBuilder, action: &str) -> Result<Value> {
+    send_json_dated(request, action).map(|(value, _)| value)
+}
+
+/// `send_json`, also returning the server's `Date` header when it has a
+/// valid one.
+pub fn send_json_dated(
+    request: RequestBuilder,
+    action: &str,
+) -> Result<(Value, Option<DateTime<FixedOffset>>)> {
+    let (http, request) = request.build_split();
+    let request = request.with_context(|| format!("failed to build {action} request"))?;
+    let method = request.method().clone();
+    let path = request.url().path().to_string();
+    if enabled!(Level::DEBUG) {
+        let body = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .map(String::from_utf8_lossy)
+            .unwrap_or_default();
+        debug!(%method, %path, body = %body, "{action} request");
+    }
+
+    let started = Instant::now();
+    let response = http
+        .execute(request)
+        .with_context(|| format!("failed to request {action}"))?;
+    let latency = started.elapsed();
+    metrics::observe_http(&path, latency.as_secs_f64());
+    let status = response.status();
+    let date = response
+        .headers()
+        .get(DATE)
+        .and_then(|date| date.to_str().ok())
+        .and_then(|date| DateTime::parse_from_rfc2822(date).ok());
+    let text = response
+        .text()
+        .with_context(|| format!("invalid {action} response"))?;
+    let parsed = serde_json::from_str::<Value>(&text);
+    if let Ok(value) = &parsed {
+        register_fields(value);
+    }
+    debug!(
+        %method,
+        %path,
+        status = status.as_u16(),
+        latency_ms = latency.as_millis() as u64,
+        body = %text,
+        "{action} response"
+    );
+
+    let value = match parsed {
+        Ok(value) => value,
+        Err(error) => {
+            if let Some(challenge) = challenge::detect_page(action, status.as_u16(), &text) {
+                return Err(ChallengeError(challenge).into());
+            }
+            return Err(error).with_context(|| format!("invalid {action} response"));
+        }
+    };
+    match challenge::detect(action, &value) {
+        Some(challenge) => Err(ChallengeError(challenge).into()),
+        None => Ok((value, date)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use reqwest::blocking::Client;
+
+    use super::*;
+
+    /// Answers one HTTP request with `body` as JSON.
+    fn serve_once(body: &'static str) -> (String, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!(
+            "http://{}/web/v1/user/auth/generate_cred_by_code",
+            listener.local_addr().unwrap()
+        );
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut length = 0;
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                line.clear();
+            }
+            reader.read_exact(&mut vec![0; length]).unwrap();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            (&stream).write_all(response.as_bytes()).unwrap();
+        });
+        (url, handle)
+    }
+
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn verbose_cred_exchange_never_shows_the_cred() {
+        let (url, server) = serve_once(
+            r#"{"code": 0, "message": "OK", "data": {"cred": "cred-f00dfeedcafe4242", "token": "sign-token-0badc0de1234"}}"#,
+        );
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_env_filter(EnvFilter::new("skas=debug"))
+            .with_writer(move || RedactingWriter(writer.clone()))
+            .finish();
+
+        let response = tracing::subscriber::with_default(subscriber, || {
+            send_json(
+                Client::new()
+                    .post(url)
+                    .body(r#"{"code": "grant", "kind": 1}"#),
+                "cred",
+            )
+        })
+        .unwrap();
+        server.join().unwrap();
+
+        assert_eq!(response["data"]["cred"], "cred-f00dfeedcafe4242");
+        let output = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("cred response"), "{output}");
+        assert!(!output.contains("cred-f00dfeedcafe4242"), "{output}");
+        assert!(!output.contains("sign-token-0badc0de1234"), "{output}");
+        assert!(output.contains("cred***4242"), "{output}");
+    }
+}
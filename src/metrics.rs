@@ -0,0 +1,345 @@
+// Copyright 2026 YuzakiKokuban
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Prometheus metrics for a run, pushed to a Pushgateway when
+//! `METRICS_PUSHGATEWAY_URL` is set. Sign results come from the finished
+//! `RunReport`; login and device-profile failures and HTTP latencies are
+//! counted as they happen, since no report keeps them.
+//!
+//! Every value describes the last run only, which is what a Pushgateway
+//! group holds anyway, so all series are gauges apart from the latency
+//! histogram. The exception is each character's last successful sign,
+//! which lives in a group of its own so that runs which do not sign that
+//! character in leave it alone.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result, bail};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE;
+use reqwest::blocking::Client;
+
+use crate::config::non_empty_env;
+use crate::report::{RunReport, SignStatus};
+
+/// Upper bounds in seconds, sized for calls that usually take 100ms-2s.
+const LATENCY_BUCKETS: [f64; 9] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+#[derive(Default)]
+struct Registry {
+    login_failures: u64,
+    device_profile_failures: u64,
+    /// Keyed by URL path, which is stable across accounts unlike full URLs.
+    http: BTreeMap<String, Histogram>,
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    login_failures: 0,
+    device_profile_failures: 0,
+    http: BTreeMap::new(),
+});
+
+fn registry() -> std::sync::MutexGuard<'static, Registry> {
+    REGISTRY
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub fn observe_http(path: &str, seconds: f64) {
+    let mut registry = registry();
+    let histogram = registry.http.entry(path.to_string()).or_default();
+    for (bucket, bound) in histogram.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+        if seconds <= bound {
+            *bucket += 1;
+        }
+    }
+    histogram.count += 1;
+    histogram.sum += seconds;
+}
+
+pub fn record_login_failure() {
+    registry().login_failures += 1;
+}
+
+pub fn record_device_profile_failure() {
+    registry().device_profile_failures += 1;
+}
+
+/// Renders the Prometheus text exposition format.
+pub fn render(run: &RunReport) -> String {
+    let mut attempts = BTreeMap::<String, u64>::new();
+    let mut outcomes = BTreeMap::<(String, &str), u64>::new();
+    for account in &run.accounts {
+        for record in &account.records {
+            *attempts.entry(record.app_code.clone()).or_default() += 1;
+            *outcomes
+                .entry((record.app_code.clone(), record.status.as_str()))
+                .or_default() += 1;
+        }
+    }
+
+    let registry = registry();
+    let mut out = String::new();
+
+    header(
+        &mut out,
+        "skas_sign_attempts",
+        "gauge",
+        "Sign calls made in the last run.",
+    );
+    for (game, count) in &attempts {
+        let _ = writeln!(
+            out,
+            "skas_sign_attempts{} {count}",
+            labels(&[("game", game)])
+        );
+    }
+
+    header(
+        &mut out,
+        "skas_sign_outcomes",
+        "gauge",
+        "Sign results in the last run by game and status.",
+    );
+    for ((game, status), count) in &outcomes {
+        let _ = writeln!(
+            out,
+            "skas_sign_outcomes{} {count}",
+            labels(&[("game", game), ("status", status)])
+        );
+    }
+
+    header(
+        &mut out,
+        "skas_accounts",
+        "gauge",
+        "Accounts processed in the last run.",
+    );
+    let _ = writeln!(out, "skas_accounts {}", run.accounts.len());
+
+    header(
+        &mut out,
+        "skas_login_failures",
+        "gauge",
+        "Failed Hypergryph/Skyland logins in the last run.",
+    );
+    let _ = writeln!(out, "skas_login_failures {}", registry.login_failures);
+
+    header(
+        &mut out,
+        "skas_device_profile_failures",
+        "gauge",
+        "Failed Shumei device profile requests in the last run.",
+    );
+    let _ = writeln!(
+        out,
+        "skas_device_profile_failures {}",
+        registry.device_profile_failures
+    );
+
+    header(
+        &mut out,
+        "skas_http_request_duration_seconds",
+        "histogram",
+        "Latency of API calls by endpoint path.",
+    );
+    for (path, histogram) in &registry.http {
+        for (count, bound) in histogram.buckets.iter().zip(LATENCY_BUCKETS) {
+            let _ = writeln!(
+                out,
+                "skas_http_request_duration_seconds_bucket{} {count}",
+                labels(&[("endpoint", path), ("le", &bound.to_string())])
+            );
+        }
+        let _ = writeln!(
+            out,
+            "skas_http_request_duration_seconds_bucket{} {}",
+            labels(&[("endpoint", path), ("le", "+Inf")]),
+            histogram.count
+        );
+        let endpoint = labels(&[("endpoint", path)]);
+        let _ = writeln!(
+            out,
+            "skas_http_request_duration_seconds_sum{endpoint} {}",
+            histogram.sum
+        );
+        let _ = writeln!(
+            out,
+            "skas_http_request_duration_seconds_count{endpoint} {}",
+            histogram.count
+        );
+    }
+
+    header(
+        &mut out,
+        "skas_last_run_timestamp_seconds",
+        "gauge",
+        "When the last run finished.",
+    );
+    let _ = writeln!(
+        out,
+        "skas_last_run_timestamp_seconds {}",
+        run.finished_at.timestamp()
+    );
+    out
+}
+
+/// One Pushgateway group per character signed in (or already signed in) by
+/// this run, as grouping-key path and body. The `account`, `game` and
+/// `character` labels come from the grouping key.
+fn last_success_groups(run: &RunReport) -> Vec<(String, String)> {
+    let mut groups = Vec::new();
+    for account in &run.accounts {
+        for record in &account.records {
+            if !matches!(record.status, SignStatus::Success | SignStatus::Info) {
+                continue;
+            }
+            let path = [
+                ("account", account.label.as_str()),
+                ("game", record.app_code.as_str()),
+                ("character", record.display_name.as_str()),
+            ]
+            .iter()
+            .map(|(name, value)| format!("{name}@base64/{}", grouping_value(value)))
+            .collect::<Vec<_>>()
+            .join("/");
+
+            let mut body = String::new();
+            header(
+                &mut body,
+                "skas_last_sign_success_timestamp_seconds",
+                "gauge",
+                "When each character was last signed in successfully.",
+            );
+            let _ = writeln!(
+                body,
+                "skas_last_sign_success_timestamp_seconds {}",
+                account.finished_at.timestamp()
+            );
+            groups.push((path, body));
+        }
+    }
+    groups
+}
+
+/// Replaces the `METRICS_JOB` group (default `skas`) on the Pushgateway at
+/// `METRICS_PUSHGATEWAY_URL`, and the last-success group of every character
+/// signed in; does nothing when that is unset.
+pub fn push(http: &Client, run: &RunReport) -> Result<()> {
+    let Some(url) = non_empty_env("METRICS_PUSHGATEWAY_URL") else {
+        return Ok(());
+    };
+    let job = non_empty_env("METRICS_JOB").unwrap_or_else(|| "skas".to_string());
+    let url = format!("{}/metrics/job/{job}", url.trim_end_matches('/'));
+
+    put_group(http, &url, render(run))?;
+    for (group, body) in last_success_groups(run) {
+        put_group(http, &format!("{url}/{group}"), body)?;
+    }
+    Ok(())
+}
+
+fn put_group(http: &Client, url: &str, body: String) -> Result<()> {
+    let response = http
+        .put(url)
+        .header("Content-Type", "text/plain; version=0.0.4")
+        .body(body)
+        .send()
+        .context("failed to push metrics")?;
+    let status = response.status();
+    if !status.is_success() {
+        bail!(
+            "Pushgateway answered HTTP {status}: {}",
+            response.text().unwrap_or_default().trim()
+        );
+    }
+    Ok(())
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// Pushgateway's `@base64` encoding, which copes with `/` in names; `=`
+/// stands for an empty value.
+fn grouping_value(value: &str) -> String {
+    if value.is_empty() {
+        "=".to_string()
+    } else {
+        URL_SAFE.encode(value)
+    }
+}
+
+fn labels(pairs: &[(&str, &str)]) -> String {
+    let rendered = pairs
+        .iter()
+        .map(|(key, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{key}=\"{value}\"")
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    format!("{{{rendered}}}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{AccountReport, SignRecord};
+
+    #[test]
+    fn render_counts_sign_results_per_game_and_status() {
+        let mut account = AccountReport::new(1, "alice");
+        for (name, status) in [
+            ("Amiya", SignStatus::Success),
+            ("\"Kal\"", SignStatus::Info),
+        ] {
+            account.push_record(SignRecord {
+                app_code: "arknights".to_string(),
+                display_name: name.to_string(),
+                status,
+                message: String::new(),
+                awards: Vec::new(),
+                challenge: None,
+            });
+        }
+        let mut run = RunReport::new();
+        run.accounts.push(account);
+        observe_http("/api/v1/game/attendance", 0.3);
+
+        let text = render(&run);
+        assert!(text.contains("skas_sign_attempts{game=\"arknights\"} 2\n"));
+        assert!(text.contains("skas_sign_outcomes{game=\"arknights\",status=\"INFO\"} 1\n"));
+        assert!(!text.contains("skas_last_sign_success_timestamp_seconds"));
+        assert!(text.contains(
+            "skas_http_request_duration_seconds_bucket{endpoint=\"/api/v1/game/attendance\",le=\"0.25\"} 0\n"
+        ));
+        assert!(text.contains(
+            "skas_http_request_duration_seconds_bucket{endpoint=\"/api/v1/game/attendance\",le=\"0.5\"} 1\n"
+        ));
+
+        let groups = last_success_groups(&run);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(
+            groups[1].0,
+            "account@base64/YWxpY2U=/game@base64/YXJrbmlnaHRz/character@base64/IkthbCI="
+        );
+        let timestamp = run.accounts[0].finished_at.timestamp();
+        assert!(groups[0].1.ends_with(&format!(
+            "skas_last_sign_success_timestamp_seconds {timestamp}\n"
+        )));
+    }
+}
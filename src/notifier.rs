@@ -1,43 +1,720 @@
 // Copyright 2026 YuzakiKokuban
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use anyhow::Result;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use hmac::digest::KeyInit;
+use hmac::{Hmac, Mac};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use reqwest::Url;
 use reqwest::blocking::Client;
+use serde_json::Value;
+use sha2::Sha256;
+use tracing::warn;
 
+use crate::cards;
+use crate::config::{
+    MessageFormat, NotifyChannel, NotifyFilter, NotifyTarget, SmtpConfig, SmtpTls,
+};
+use crate::redact::redact;
+use crate::report::{AccountReport, RunReport};
+use crate::state::RunState;
+use crate::system::{Clock, SystemClock};
+use crate::template::Template;
 use crate::utils::{object_from_pairs, python_json_string};
 
-pub fn send_webhook(http: &Client, url: &str, content: &str) -> Result<()> {
+type HmacSha256 = Hmac<Sha256>;
+
+const DELIVERY_ATTEMPTS: u32 = 3;
+const DELIVERY_RETRY_DELAY: Duration = Duration::from_secs(2);
+const SMTP_TIMEOUT: Duration = Duration::from_secs(15);
+const REPORT_TITLE: &str = "SKAS Report";
+/// Reproduces the plain log lines printed to stdout.
+const DEFAULT_TEXT_TEMPLATE: &str = "{{#accounts}}{{#logs}}{{.}}\n{{/logs}}{{/accounts}}";
+const DEFAULT_MARKDOWN_TEMPLATE: &str = "\
+### {{title}}
+{{#accounts}}
+
+#### {{label}}
+{{#characters}}
+- [{{game}}] {{name}}: **{{status}}** - {{message}}{{#awards_text}} | 获得: {{awards_text}}{{/awards_text}}
+{{/characters}}
+{{#error}}
+- {{error}}
+{{/error}}
+{{/accounts}}
+";
+/// Provider error codes that mean "slow down" rather than "bad request":
+/// DingTalk 130101, WeCom 45009 and Feishu 11232 / 9499.
+const RATE_LIMIT_CODES: [i64; 4] = [130101, 45009, 11232, 9499];
+
+pub struct NotifyMessage<'a> {
+    pub title: String,
+    /// Rendered template; the whole message for text and markdown, the
+    /// fallback for generic endpoints when `format` is `Card`.
+    pub body: String,
+    pub format: MessageFormat,
+    pub accounts: &'a [&'a AccountReport],
+    pub mention_all: bool,
+}
+
+enum Delivery {
+    Delivered,
+    Retry(anyhow::Error),
+    Rejected(anyhow::Error),
+}
+
+/// Accounts a target should hear about in the run report: those it is
+/// routed to that also pass its filter. `token_expired` targets get nothing
+/// here; they are served by the dedicated alerts from `notify_token_expired`.
+pub fn select_reports<'a>(
+    target: &NotifyTarget,
+    reports: &'a [AccountReport],
+    state: &RunState,
+) -> Vec<&'a AccountReport> {
+    reports
+        .iter()
+        .filter(|report| is_routed(target, report))
+        .filter(|report| match target.filter {
+            NotifyFilter::Always => true,
+            NotifyFilter::Failure => !report.success,
+            NotifyFilter::TokenExpired => false,
+            NotifyFilter::Changed => state.changed(report),
+        })
+        .collect()
+}
+
+/// Accounts routed to `target` whose token has expired, whatever its filter.
+pub fn expired_accounts<'a>(
+    target: &NotifyTarget,
+    reports: &'a [AccountReport],
+) -> Vec<&'a AccountReport> {
+    reports
+        .iter()
+        .filter(|report| report.token_expired() && is_routed(target, report))
+        .collect()
+}
+
+fn is_routed(target: &NotifyTarget, report: &AccountReport) -> bool {
+    target.accounts.is_empty()
+        || target
+            .accounts
+            .iter()
+            .any(|selector| report.matches(selector))
+}
+
+/// A standalone alert naming the account, sent ahead of the run report so an
+/// expired token cannot get lost among routine results.
+pub fn notify_token_expired(
+    http: &Client,
+    target: &NotifyTarget,
+    account: &AccountReport,
+) -> Result<()> {
+    let title = format!(
+        "⚠️ SKAS {} 已过期: {}",
+        account.credential_kind, account.label
+    );
+    let notice = format!(
+        "账号「{}」的{} 已过期，请尽快重新获取并更新，以免错过签到奖励。",
+        account.label, account.credential_kind
+    );
+    let detail = account.error.clone().unwrap_or_default();
+    let text = format!("{title}\n{notice}\n{detail}").trim().to_string();
+
+    match &target.channel {
+        NotifyChannel::Webhook { url, secret } => {
+            let (format, body) = match target.format {
+                MessageFormat::Text => (MessageFormat::Text, text),
+                MessageFormat::Markdown | MessageFormat::Card => (
+                    MessageFormat::Markdown,
+                    format!("### {title}\n\n{notice}\n\n> {detail}"),
+                ),
+            };
+            let message = NotifyMessage {
+                title,
+                body,
+                format,
+                accounts: &[account],
+                mention_all: target.mention_all,
+            };
+            send_webhook(http, url, secret.as_deref(), &message)
+        }
+        NotifyChannel::Smtp(config) => send_email(config, &title, &text, &[account]),
+    }
+}
+
+pub fn notify(
+    http: &Client,
+    target: &NotifyTarget,
+    run: &RunReport,
+    reports: &[&AccountReport],
+) -> Result<()> {
+    let title = match &target.title {
+        Some(title) => title.render(&run.context(REPORT_TITLE, reports)),
+        None => REPORT_TITLE.to_string(),
+    };
+    let title = title.trim().to_string();
+    let context = run.context(&title, reports);
+
+    match &target.channel {
+        NotifyChannel::Webhook { url, secret } => {
+            let message = NotifyMessage {
+                body: render_template(target.template.as_ref(), target.format, &context)?,
+                title,
+                format: target.format,
+                accounts: reports,
+                mention_all: false,
+            };
+            send_webhook(http, url, secret.as_deref(), &message)
+        }
+        NotifyChannel::Smtp(config) => {
+            let text = match &target.template {
+                Some(template) => template.render(&context).trim().to_string(),
+                None => render_text(reports),
+            };
+            send_email(config, &title, &text, reports)
+        }
+    }
+}
+
+fn render_template(
+    template: Option<&Template>,
+    format: MessageFormat,
+    context: &Value,
+) -> Result<String> {
+    let rendered = match template {
+        Some(template) => template.render(context),
+        None => {
+            let source = match format {
+                MessageFormat::Text | MessageFormat::Card => DEFAULT_TEXT_TEMPLATE,
+                MessageFormat::Markdown => DEFAULT_MARKDOWN_TEMPLATE,
+            };
+            Template::parse(source)?.render(context)
+        }
+    };
+    Ok(rendered.trim().to_string())
+}
+
+pub fn send_webhook(
+    http: &Client,
+    url: &str,
+    secret: Option<&str>,
+    message: &NotifyMessage,
+) -> Result<()> {
     if url.trim().is_empty() {
         return Ok(());
     }
 
-    let payload = object_from_pairs([
-        ("msgtype", serde_json::Value::String("text".to_string())),
-        (
-            "text",
-            object_from_pairs([("content", serde_json::Value::String(content.to_string()))]),
-        ),
-        (
-            "title",
-            serde_json::Value::String("SKAS Report".to_string()),
-        ),
-        ("body", serde_json::Value::String(content.to_string())),
-    ]);
-
-    let body = if url.contains("dingtalk") || url.contains("feishu") {
-        python_json_string(&payload)
-    } else {
-        python_json_string(&object_from_pairs([
-            ("content", serde_json::Value::String(content.to_string())),
-            ("message", serde_json::Value::String(content.to_string())),
-        ]))
-    };
+    let url = Url::parse(url.trim()).context("invalid webhook url")?;
+    let platform = Platform::detect(&url);
+    let payload = message.payload(platform);
+    let secret = secret.map(str::trim).filter(|secret| !secret.is_empty());
+    with_retry("webhook", || {
+        deliver_webhook(http, &url, platform, secret, &payload)
+    })
+}
+
+pub fn send_email(
+    config: &SmtpConfig,
+    subject: &str,
+    text: &str,
+    reports: &[&AccountReport],
+) -> Result<()> {
+    let from = config
+        .from
+        .parse::<Mailbox>()
+        .with_context(|| format!("invalid from address: {}", config.from))?;
+    let mut builder = Message::builder().from(from).subject(redact(subject));
+    for to in &config.to {
+        builder = builder.to(to
+            .parse::<Mailbox>()
+            .with_context(|| format!("invalid recipient address: {to}"))?);
+    }
+    let message = builder
+        .multipart(MultiPart::alternative_plain_html(
+            redact(text),
+            redact(&render_html(subject, reports)),
+        ))
+        .context("failed to build email")?;
+
+    let mut transport = match config.tls {
+        SmtpTls::StartTls => SmtpTransport::starttls_relay(&config.host)?,
+        SmtpTls::Implicit => SmtpTransport::relay(&config.host)?,
+        SmtpTls::None => SmtpTransport::builder_dangerous(&config.host),
+    }
+    .port(config.port)
+    .timeout(Some(SMTP_TIMEOUT));
+    if let (Some(username), Some(password)) = (&config.username, &config.password) {
+        transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+    }
+    let transport = transport.build();
+
+    with_retry("email", || {
+        Ok(match transport.send(&message) {
+            Ok(_) => Delivery::Delivered,
+            Err(error) if error.is_permanent() => {
+                Delivery::Rejected(anyhow!(error).context("SMTP server rejected the email"))
+            }
+            Err(error) => Delivery::Retry(anyhow!(error).context("failed to send email")),
+        })
+    })
+}
+
+fn with_retry(kind: &str, mut deliver: impl FnMut() -> Result<Delivery>) -> Result<()> {
+    let mut attempt = 1;
+    loop {
+        match deliver()? {
+            Delivery::Delivered => return Ok(()),
+            Delivery::Retry(error) if attempt < DELIVERY_ATTEMPTS => {
+                warn!("Notify {kind} attempt {attempt}/{DELIVERY_ATTEMPTS} failed: {error:#}");
+                thread::sleep(DELIVERY_RETRY_DELAY * attempt);
+                attempt += 1;
+            }
+            Delivery::Retry(error) | Delivery::Rejected(error) => {
+                return Err(
+                    error.context(format!("{kind} delivery failed after {attempt} attempt(s)"))
+                );
+            }
+        }
+    }
+}
+
+fn render_text(reports: &[&AccountReport]) -> String {
+    reports
+        .iter()
+        .map(|report| format!("== {} ==\n{}", report.label, report.logs.join("\n")))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn render_html(title: &str, reports: &[&AccountReport]) -> String {
+    let sections = reports
+        .iter()
+        .map(|report| {
+            let items = report
+                .logs
+                .iter()
+                .map(|log| format!("<li>{}</li>", escape_html(log)))
+                .collect::<String>();
+            format!("<h3>{}</h3><ul>{items}</ul>", escape_html(&report.label))
+        })
+        .collect::<String>();
+    format!(
+        "<html><body><h2>{}</h2>{sections}</body></html>",
+        escape_html(title)
+    )
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Platform {
+    DingTalk,
+    Feishu,
+    WeCom,
+    Generic,
+}
+
+impl Platform {
+    fn detect(url: &Url) -> Self {
+        let host = url.host_str().unwrap_or_default();
+        if host.contains("dingtalk") {
+            Self::DingTalk
+        } else if host.contains("feishu") || host.contains("larksuite") {
+            Self::Feishu
+        } else if host.contains("qyapi.weixin.qq.com") {
+            Self::WeCom
+        } else {
+            Self::Generic
+        }
+    }
+}
+
+impl NotifyMessage<'_> {
+    fn payload(&self, platform: Platform) -> Value {
+        let mut payload = self.base_payload(platform);
+        if !self.mention_all {
+            return payload;
+        }
+
+        match (platform, &mut payload) {
+            (Platform::DingTalk, Value::Object(map)) => {
+                map.insert(
+                    "at".to_string(),
+                    object_from_pairs([("isAtAll", Value::Bool(true))]),
+                );
+            }
+            (Platform::WeCom, Value::Object(map)) => {
+                if let Some(Value::Object(text)) = map.get_mut("text") {
+                    text.insert(
+                        "mentioned_list".to_string(),
+                        Value::Array(vec![Value::String("@all".to_string())]),
+                    );
+                }
+            }
+            _ => {}
+        }
+        payload
+    }
 
-    let _ = http
+    fn base_payload(&self, platform: Platform) -> Value {
+        let text = || {
+            if self.mention_all && platform == Platform::Feishu {
+                let mention = if self.format == MessageFormat::Text {
+                    "<at user_id=\"all\">所有人</at>"
+                } else {
+                    "<at id=all></at>"
+                };
+                Value::String(format!("{mention} {}", self.body))
+            } else {
+                Value::String(self.body.clone())
+            }
+        };
+        let title = || Value::String(self.title.clone());
+
+        match (platform, self.format) {
+            (Platform::Generic, _) => object_from_pairs([("content", text()), ("message", text())]),
+            (Platform::Feishu, MessageFormat::Text) => object_from_pairs([
+                ("msg_type", Value::String("text".to_string())),
+                ("content", object_from_pairs([("text", text())])),
+            ]),
+            (Platform::Feishu, MessageFormat::Markdown) => object_from_pairs([
+                ("msg_type", Value::String("interactive".to_string())),
+                (
+                    "card",
+                    object_from_pairs([
+                        (
+                            "header",
+                            object_from_pairs([(
+                                "title",
+                                object_from_pairs([
+                                    ("tag", Value::String("plain_text".to_string())),
+                                    ("content", title()),
+                                ]),
+                            )]),
+                        ),
+                        (
+                            "elements",
+                            Value::Array(vec![object_from_pairs([
+                                ("tag", Value::String("markdown".to_string())),
+                                ("content", text()),
+                            ])]),
+                        ),
+                    ]),
+                ),
+            ]),
+            (Platform::Feishu, MessageFormat::Card) => {
+                cards::feishu_card(&self.title, self.accounts, self.mention_all)
+            }
+            (_, MessageFormat::Text) => object_from_pairs([
+                ("msgtype", Value::String("text".to_string())),
+                ("text", object_from_pairs([("content", text())])),
+                ("title", title()),
+                ("body", text()),
+            ]),
+            (Platform::WeCom, format) => {
+                let content = if format == MessageFormat::Card {
+                    cards::wecom_markdown(&self.title, self.accounts)
+                } else {
+                    self.body.clone()
+                };
+                object_from_pairs([
+                    ("msgtype", Value::String("markdown".to_string())),
+                    (
+                        "markdown",
+                        object_from_pairs([("content", Value::String(content))]),
+                    ),
+                ])
+            }
+            (_, format) => {
+                let content = if format == MessageFormat::Card {
+                    cards::dingtalk_markdown(&self.title, self.accounts)
+                } else {
+                    self.body.clone()
+                };
+                object_from_pairs([
+                    ("msgtype", Value::String("markdown".to_string())),
+                    (
+                        "markdown",
+                        object_from_pairs([("title", title()), ("text", Value::String(content))]),
+                    ),
+                ])
+            }
+        }
+    }
+}
+
+fn deliver_webhook(
+    http: &Client,
+    url: &Url,
+    platform: Platform,
+    secret: Option<&str>,
+    payload: &Value,
+) -> Result<Delivery> {
+    let mut url = url.clone();
+    let mut payload = payload.clone();
+    if let Some(secret) = secret {
+        match platform {
+            Platform::DingTalk => {
+                let (timestamp, sign) = dingtalk_signature(secret, &SystemClock)?;
+                url.query_pairs_mut()
+                    .append_pair("timestamp", &timestamp)
+                    .append_pair("sign", &sign);
+            }
+            Platform::Feishu => {
+                if let Value::Object(map) = &mut payload {
+                    let (timestamp, sign) = feishu_signature(secret, &SystemClock)?;
+                    map.insert("timestamp".to_string(), Value::String(timestamp));
+                    map.insert("sign".to_string(), Value::String(sign));
+                }
+            }
+            Platform::WeCom | Platform::Generic => {}
+        }
+    }
+    let body = redact(&python_json_string(&payload));
+
+    let response = match http
         .post(url)
         .header("Content-Type", "application/json")
         .body(body)
-        .send()?;
+        .send()
+    {
+        Ok(response) => response,
+        Err(error) => {
+            return Ok(Delivery::Retry(
+                anyhow!(error).context("failed to send webhook"),
+            ));
+        }
+    };
 
-    Ok(())
+    let status = response.status();
+    let text = response.text().unwrap_or_default();
+    if status.as_u16() == 429 || status.is_server_error() {
+        return Ok(Delivery::Retry(anyhow!("HTTP {status}: {}", text.trim())));
+    }
+    if !status.is_success() {
+        return Ok(Delivery::Rejected(anyhow!(
+            "HTTP {status}: {}",
+            text.trim()
+        )));
+    }
+
+    Ok(check_provider_response(platform == Platform::Feishu, &text))
+}
+
+/// DingTalk and WeCom answer HTTP 200 with a non-zero `errcode` on failure,
+/// Feishu with a non-zero `code` (or `StatusCode` on legacy hooks). Generic
+/// endpoints only fail on `errcode`, since many use `code: 200` for success.
+fn check_provider_response(feishu: bool, text: &str) -> Delivery {
+    let Ok(response) = serde_json::from_str::<Value>(text) else {
+        return Delivery::Delivered;
+    };
+
+    let code_keys: &[&str] = if feishu {
+        &["code", "StatusCode"]
+    } else {
+        &["errcode"]
+    };
+    let code = code_keys
+        .iter()
+        .find_map(|key| response.get(*key).and_then(Value::as_i64));
+    let Some(code) = code.filter(|code| *code != 0) else {
+        return Delivery::Delivered;
+    };
+
+    let message = ["errmsg", "msg", "StatusMessage", "message"]
+        .iter()
+        .find_map(|key| response.get(*key).and_then(Value::as_str))
+        .unwrap_or("unknown error");
+    let error = anyhow!("provider error {code}: {message}");
+    if RATE_LIMIT_CODES.contains(&code) {
+        Delivery::Retry(error)
+    } else {
+        Delivery::Rejected(error)
+    }
+}
+
+/// DingTalk signs `"{timestamp}\n{secret}"` with the secret as key; the
+/// millisecond timestamp and the base64 digest go into the query string.
+fn dingtalk_signature(secret: &str, clock: &dyn Clock) -> Result<(String, String)> {
+    let timestamp = clock.unix_millis().to_string();
+    let string_to_sign = format!("{timestamp}\n{secret}");
+
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).context("invalid HMAC key")?;
+    mac.update(string_to_sign.as_bytes());
+    Ok((timestamp, STANDARD.encode(mac.finalize().into_bytes())))
+}
+
+/// Feishu uses `"{timestamp}\n{secret}"` itself as the HMAC key over an empty
+/// message, with a timestamp in seconds; both fields are sent in the body.
+fn feishu_signature(secret: &str, clock: &dyn Clock) -> Result<(String, String)> {
+    let timestamp = clock.unix_seconds().to_string();
+    let string_to_sign = format!("{timestamp}\n{secret}");
+
+    let mac = HmacSha256::new_from_slice(string_to_sign.as_bytes()).context("invalid HMAC key")?;
+    Ok((timestamp, STANDARD.encode(mac.finalize().into_bytes())))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+    use crate::system::fixed::FixedClock;
+
+    #[test]
+    fn webhook_signatures_match_reference() {
+        let clock = FixedClock(1_767_294_245_678);
+        let (timestamp, sign) = dingtalk_signature("SEC0123456789abcdef", &clock).unwrap();
+        assert_eq!(timestamp, "1767294245678");
+        assert_eq!(sign, "Dnc+ukKuCxmTk5IENhtwWJps5PJlz8uIBqwFjbuhCBE=");
+
+        let (timestamp, sign) = feishu_signature("SEC0123456789abcdef", &clock).unwrap();
+        assert_eq!(timestamp, "1767294245");
+        assert_eq!(sign, "QjdDMYhccPt86Fm/K9K63qh+KIdLqpHojtvGiYzHG+U=");
+    }
+
+    #[test]
+    fn webhook_payload_shape_per_platform() {
+        let account = AccountReport::new(1, "main");
+        let accounts = [&account];
+        let message = |format| NotifyMessage {
+            title: "森空岛签到".to_string(),
+            body: "done".to_string(),
+            format,
+            accounts: &accounts,
+            mention_all: false,
+        };
+
+        let dingtalk = message(MessageFormat::Card).payload(Platform::DingTalk);
+        assert_eq!(dingtalk["msgtype"], "markdown");
+        assert_eq!(dingtalk["markdown"]["title"], "森空岛签到");
+        assert!(
+            dingtalk["markdown"]["text"]
+                .as_str()
+                .unwrap()
+                .starts_with("### 森空岛签到")
+        );
+
+        let wecom = message(MessageFormat::Card).payload(Platform::WeCom);
+        assert_eq!(wecom["msgtype"], "markdown");
+        assert!(
+            wecom["markdown"]["content"]
+                .as_str()
+                .unwrap()
+                .contains("**👤 main**")
+        );
+
+        let feishu = message(MessageFormat::Card).payload(Platform::Feishu);
+        assert_eq!(feishu["msg_type"], "interactive");
+        assert_eq!(feishu["card"]["header"]["template"], "green");
+        let feishu_mention = NotifyMessage {
+            mention_all: true,
+            ..message(MessageFormat::Card)
+        }
+        .payload(Platform::Feishu);
+        assert_eq!(
+            feishu_mention["card"]["elements"][0]["text"]["content"],
+            "<at id=all></at>"
+        );
+        // Feishu's text messages use its own shape, not DingTalk's.
+        let feishu_text = message(MessageFormat::Text).payload(Platform::Feishu);
+        assert_eq!(
+            python_json_string(&feishu_text),
+            r#"{"msg_type": "text", "content": {"text": "done"}}"#
+        );
+
+        let generic = message(MessageFormat::Card).payload(Platform::Generic);
+        assert_eq!(
+            python_json_string(&generic),
+            r#"{"content": "done", "message": "done"}"#
+        );
+    }
+
+    /// Minimal SMTP sink: accepts one session and returns the transcript.
+    fn spawn_smtp_sink() -> (u16, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut transcript = String::new();
+            writer.write_all(b"220 sink ESMTP\r\n").unwrap();
+
+            let mut in_data = false;
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 {
+                transcript.push_str(&line);
+                let command = line.trim_end().to_ascii_uppercase();
+                let reply: &[u8] = if in_data {
+                    if command == "." {
+                        in_data = false;
+                        b"250 queued\r\n"
+                    } else {
+                        b""
+                    }
+                } else if command.starts_with("EHLO") {
+                    b"250-sink\r\n250 AUTH PLAIN LOGIN\r\n"
+                } else if command.starts_with("AUTH") {
+                    b"235 authenticated\r\n"
+                } else if command == "DATA" {
+                    in_data = true;
+                    b"354 go ahead\r\n"
+                } else if command == "QUIT" {
+                    writer.write_all(b"221 bye\r\n").unwrap();
+                    break;
+                } else {
+                    b"250 ok\r\n"
+                };
+                writer.write_all(reply).unwrap();
+                line.clear();
+            }
+            transcript
+        });
+        (port, handle)
+    }
+
+    #[test]
+    fn send_email_delivers_multipart_report_to_all_recipients() {
+        let (port, sink) = spawn_smtp_sink();
+        let config = SmtpConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            tls: SmtpTls::None,
+            username: Some("user".to_string()),
+            password: Some("pass".to_string()),
+            from: "SKAS <skas@example.com>".to_string(),
+            to: vec!["a@example.com".to_string(), "b@example.com".to_string()],
+        };
+        let mut report = AccountReport::new(1, "alice");
+        report
+            .logs
+            .push("[ARKNIGHTS] Doctor: SUCCESS - <ok>".to_string());
+
+        send_email(&config, "SKAS Report", &render_text(&[&report]), &[&report]).unwrap();
+        let transcript = sink.join().unwrap();
+
+        assert!(transcript.contains("AUTH PLAIN"));
+        assert!(transcript.contains("RCPT TO:<a@example.com>"));
+        assert!(transcript.contains("RCPT TO:<b@example.com>"));
+        assert!(transcript.contains("multipart/alternative"));
+        assert!(transcript.contains("text/plain"));
+        assert!(transcript.contains("text/html"));
+        assert!(transcript.contains("&lt;ok&gt;"));
+    }
 }
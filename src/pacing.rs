@@ -0,0 +1,147 @@
+// Copyright 2026 YuzakiKokuban
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Randomized timing, so a run looks less like a script firing requests
+//! back to back at the same second every day.
+//!
+//! Ranges are written `min-max` (or a single value) in seconds:
+//! `PACING_DELAY` pauses before fetching bindings and before each sign,
+//! `ACCOUNT_START_DELAY` waits before each account, and
+//! `SIGN_TIMESTAMP_OFFSET` is how far signature timestamps lag the clock
+//! (1-3 seconds by default, where it used to be exactly 2).
+
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use tracing::debug;
+
+use crate::config::non_empty_env;
+use crate::system::RandomSource;
+
+/// Upper bound for any range. Far beyond a sensible delay, and it keeps
+/// `Duration::from_secs_f64` from panicking on huge values.
+const MAX_SECONDS: f64 = 3600.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Range {
+    pub min: f64,
+    pub max: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pacing {
+    pub step: Range,
+    pub account_start: Range,
+    pub timestamp_offset: Range,
+}
+
+impl Default for Pacing {
+    fn default() -> Self {
+        Pacing {
+            step: Range::ZERO,
+            account_start: Range::ZERO,
+            timestamp_offset: Range { min: 1.0, max: 3.0 },
+        }
+    }
+}
+
+impl Range {
+    const ZERO: Range = Range { min: 0.0, max: 0.0 };
+
+    fn parse(raw: &str) -> Result<Self> {
+        let (min, max) = raw.split_once('-').unwrap_or((raw, raw));
+        let parse = |value: &str| -> Result<f64> {
+            value
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite() && *value >= 0.0)
+                .with_context(|| format!("{value:?} is not a number of seconds"))
+        };
+        let (min, max) = (parse(min)?, parse(max)?);
+        if max > MAX_SECONDS {
+            bail!("{max} is more than {MAX_SECONDS} seconds");
+        }
+        if min > max {
+            bail!("{min} is more than {max}");
+        }
+        Ok(Range { min, max })
+    }
+
+    fn sample(self, random: &mut dyn RandomSource) -> f64 {
+        if self.max <= self.min {
+            return self.min;
+        }
+        let mut bytes = [0_u8; 8];
+        random.fill_bytes(&mut bytes);
+        // The top 53 bits as a fraction in [0, 1).
+        let unit = (u64::from_le_bytes(bytes) >> 11) as f64 / (1_u64 << 53) as f64;
+        self.min + (self.max - self.min) * unit
+    }
+}
+
+impl Pacing {
+    /// Sleeps for a random step delay before `what`.
+    pub fn pause(&self, what: &str, random: &mut dyn RandomSource) {
+        sleep(self.step.sample(random), what);
+    }
+
+    /// Sleeps for a random start offset before an account.
+    pub fn stagger(&self, random: &mut dyn RandomSource) {
+        sleep(self.account_start.sample(random), "account");
+    }
+
+    /// Whole seconds to subtract from signature timestamps.
+    pub fn timestamp_offset(&self, random: &mut dyn RandomSource) -> i64 {
+        self.timestamp_offset.sample(random).round() as i64
+    }
+}
+
+fn sleep(seconds: f64, what: &str) {
+    if seconds <= 0.0 {
+        return;
+    }
+    debug!(seconds = format!("{seconds:.1}"), "Waiting before {what}");
+    thread::sleep(Duration::from_secs_f64(seconds));
+}
+
+/// Reads `PACING_DELAY`, `ACCOUNT_START_DELAY` and `SIGN_TIMESTAMP_OFFSET`.
+pub fn load_pacing() -> Result<Pacing> {
+    let mut pacing = Pacing::default();
+    for (name, range) in [
+        ("PACING_DELAY", &mut pacing.step),
+        ("ACCOUNT_START_DELAY", &mut pacing.account_start),
+        ("SIGN_TIMESTAMP_OFFSET", &mut pacing.timestamp_offset),
+    ] {
+        if let Some(raw) = non_empty_env(name) {
+            *range = Range::parse(&raw).with_context(|| format!("invalid {name}"))?;
+        }
+    }
+    Ok(pacing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::SystemRandom;
+    use crate::system::fixed::CountingRandom;
+
+    #[test]
+    fn parses_ranges_and_samples_within_them() {
+        assert_eq!(Range::parse("0.5-2").unwrap(), Range { min: 0.5, max: 2.0 });
+        assert_eq!(Range::parse(" 3 ").unwrap(), Range { min: 3.0, max: 3.0 });
+        assert!(Range::parse("5-1").is_err());
+        assert!(Range::parse("-1").is_err());
+        assert!(Range::parse("soon").is_err());
+        assert!(Range::parse("1e30").is_err());
+        assert!(Range::parse("0-3601").is_err());
+
+        let pacing = Pacing::default();
+        for _ in 0..100 {
+            assert!((1..=3).contains(&pacing.timestamp_offset(&mut SystemRandom)));
+        }
+        let sampled = pacing.timestamp_offset.sample(&mut CountingRandom(0));
+        assert!((sampled - 1.062_714_340_638_216).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,87 @@
+// Copyright 2026 YuzakiKokuban
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Which Skyland client signed requests claim to come from.
+//!
+//! `web` is what SKAS has always sent: platform 3, a WebView user agent and
+//! a `dId` from Shumei's web SDK. `android` poses as the Skyland app:
+//! platform 1, the app's version name and OkHttp user agent, and an
+//! Android ID as `dId`, derived like random device profiles from the
+//! account label and `DEVICE_PROFILE_SEED`, which is required for it, so it
+//! stays the same across runs and token rotations.
+
+use anyhow::Result;
+
+use crate::config::AccountChoice;
+
+const WEB_USER_AGENT: &str = "Mozilla/5.0 (Linux; Android 12; SKAS/1.0) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/101.0.4951.61 Mobile Safari/537.36";
+const APP_VERSION: &str = "1.21.0";
+const APP_USER_AGENT: &str =
+    "Skland/1.21.0 (com.hypergryph.skland; build:102100065; Android 31; ) Okhttp/4.11.0";
+const APP_PACKAGE: &str = "com.hypergryph.skland";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Platform {
+    Web,
+    Android { android_id: String },
+}
+
+impl Platform {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::Web => "web",
+            Platform::Android { .. } => "android",
+        }
+    }
+
+    /// The `platform` header and signed field.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Platform::Web => "3",
+            Platform::Android { .. } => "1",
+        }
+    }
+
+    /// The `vName` header and signed field.
+    pub fn v_name(&self) -> &'static str {
+        match self {
+            Platform::Web => "1.0.0",
+            Platform::Android { .. } => APP_VERSION,
+        }
+    }
+
+    pub fn user_agent(&self) -> &'static str {
+        match self {
+            Platform::Web => WEB_USER_AGENT,
+            Platform::Android { .. } => APP_USER_AGENT,
+        }
+    }
+
+    /// Both the app and its WebView send the package name.
+    pub fn requested_with(&self) -> &'static str {
+        APP_PACKAGE
+    }
+}
+
+/// How each account picks its platform from `SKYLAND_PLATFORM`.
+pub struct Platforms(AccountChoice);
+
+impl Platforms {
+    pub fn for_account(&self, label: &str) -> Result<Platform> {
+        if self.0.name(label) != "android" {
+            return Ok(Platform::Web);
+        }
+        let digest = self.0.seeded_digest("android\n", label)?;
+        Ok(Platform::Android {
+            android_id: hex::encode(&digest[..8]),
+        })
+    }
+}
+
+/// Reads `SKYLAND_PLATFORM` and `DEVICE_PROFILE_SEED`.
+pub fn load_platforms() -> Result<Platforms> {
+    AccountChoice::from_env("SKYLAND_PLATFORM", "platform", "web", "android", |name| {
+        matches!(name, "web" | "android")
+    })
+    .map(Platforms)
+}
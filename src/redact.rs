@@ -0,0 +1,161 @@
+// Copyright 2026 YuzakiKokuban
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Last line of defence against leaking credentials. Every secret the process
+//! learns (tokens, creds, the dId, webhook URLs and keys, passwords) is
+//! registered here, and everything printed or sent out goes through
+//! `redact`, which masks registered values and the token-bearing parts of
+//! webhook URLs.
+
+use std::sync::Mutex;
+
+use serde_json::Value;
+
+use crate::utils::mask_token;
+
+/// Shorter values are too likely to appear by accident to be worth masking.
+const MIN_SECRET_LEN: usize = 6;
+
+/// Query parameters and path segments that carry webhook credentials.
+const URL_MARKERS: &[&str] = &[
+    "access_token=",
+    "key=",
+    "token=",
+    "cred=",
+    "sign=",
+    "/hook/",
+];
+
+/// Response fields that hand out credentials: Skyland's `cred` and signing
+/// `token`, Hypergryph's account `token` and grant `code`, and Shumei's
+/// `deviceId`.
+const SECRET_FIELDS: &[&str] = &["cred", "token", "code", "deviceId"];
+
+static SECRETS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+pub fn register(secret: &str) {
+    let secret = secret.trim();
+    if secret.len() < MIN_SECRET_LEN {
+        return;
+    }
+    let mut secrets = SECRETS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if !secrets.iter().any(|known| known == secret) {
+        secrets.push(secret.to_string());
+        // Longest first, so a secret containing another is masked whole.
+        secrets.sort_by_key(|known| std::cmp::Reverse(known.len()));
+    }
+}
+
+/// Registers every string under a `SECRET_FIELDS` key anywhere in a
+/// response, so it is masked before the caller has had a chance to pick it
+/// out, starting with the debug log of the response itself.
+pub fn register_fields(value: &Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                match value {
+                    Value::String(secret) if SECRET_FIELDS.contains(&key.as_str()) => {
+                        register(secret)
+                    }
+                    _ => register_fields(value),
+                }
+            }
+        }
+        Value::Array(values) => values.iter().for_each(register_fields),
+        _ => {}
+    }
+}
+
+pub fn redact(text: &str) -> String {
+    let mut text = text.to_string();
+    {
+        let secrets = SECRETS
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        for secret in secrets.iter() {
+            if text.contains(secret.as_str()) {
+                text = text.replace(secret.as_str(), &mask_secret(secret));
+            }
+        }
+    }
+    for marker in URL_MARKERS {
+        text = mask_after(&text, marker);
+    }
+    text
+}
+
+fn mask_secret(secret: &str) -> String {
+    if secret.len() <= 8 || !secret.is_ascii() {
+        "***".to_string()
+    } else {
+        mask_token(secret)
+    }
+}
+
+/// Masks the value following each `marker` up to the next delimiter. Query
+/// markers only count right after `?` or `&`, so `monkey=` is left alone.
+fn mask_after(text: &str, marker: &str) -> String {
+    let is_query = marker.ends_with('=');
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find(marker) {
+        let end = index + marker.len();
+        output.push_str(&rest[..end]);
+        rest = &rest[end..];
+
+        let preceded = output[..output.len() - marker.len()]
+            .chars()
+            .next_back()
+            .is_some_and(|before| matches!(before, '?' | '&'));
+        if is_query && !preceded {
+            continue;
+        }
+        let value_len = rest
+            .find(|c: char| {
+                c == '&' || c == '/' || c == '"' || c == '\'' || c == ')' || c.is_whitespace()
+            })
+            .unwrap_or(rest.len());
+        if value_len > 0 {
+            output.push_str("***");
+        }
+        rest = &rest[value_len..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// `println!` through `redact`, for command output on stdout; diagnostics
+/// go through `tracing`, whose writer redacts on its own.
+macro_rules! outln {
+    ($($arg:tt)*) => {
+        println!("{}", $crate::redact::redact(&format!($($arg)*)))
+    };
+}
+
+pub(crate) use outln;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_registered_secrets_and_webhook_tokens() {
+        register("cred-0123456789abcdef");
+        let text = redact(
+            "cred cred-0123456789abcdef failed for url \
+             (https://oapi.dingtalk.com/robot/send?access_token=abcdef123456&timestamp=1) \
+             and https://open.feishu.cn/open-apis/bot/v2/hook/0f1e2d3c-4b5a \
+             but monkey=banana stays",
+        );
+
+        assert_eq!(
+            text,
+            "cred cred***cdef failed for url \
+             (https://oapi.dingtalk.com/robot/send?access_token=***&timestamp=1) \
+             and https://open.feishu.cn/open-apis/bot/v2/hook/*** \
+             but monkey=banana stays"
+        );
+    }
+}
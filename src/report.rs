@@ -0,0 +1,347 @@
+// Copyright 2026 YuzakiKokuban
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use chrono::{DateTime, Local};
+use serde_json::{Number, Value};
+use tracing::{error, info, warn};
+
+use crate::challenge::Challenge;
+use crate::client::TokenHealth;
+use crate::utils::object_from_pairs;
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignStatus {
+    Success,
+    Info,
+    Fail,
+    Error,
+    /// Blocked by a captcha or risk-control check.
+    Challenge,
+}
+
+#[derive(Clone, Debug)]
+pub struct Award {
+    pub name: String,
+    pub count: String,
+}
+
+/// Outcome of one character's attendance call.
+#[derive(Clone, Debug)]
+pub struct SignRecord {
+    pub app_code: String,
+    pub display_name: String,
+    pub status: SignStatus,
+    pub message: String,
+    pub awards: Vec<Award>,
+    pub challenge: Option<Challenge>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AccountReport {
+    pub index: usize,
+    pub label: String,
+    pub success: bool,
+    /// `None` when the run failed before the token could be checked.
+    pub token_health: Option<TokenHealth>,
+    /// How alerts name the account's credential, from `Credential::kind_name`.
+    pub credential_kind: &'static str,
+    pub records: Vec<SignRecord>,
+    pub error: Option<String>,
+    /// Set when login itself ran into a captcha or risk-control check.
+    pub challenge: Option<Challenge>,
+    /// Things worth knowing that are neither a sign result nor a failure,
+    /// such as a cred refresh in the middle of the run.
+    pub events: Vec<String>,
+    pub logs: Vec<String>,
+    pub started_at: DateTime<Local>,
+    pub finished_at: DateTime<Local>,
+}
+
+pub struct RunReport {
+    pub started_at: DateTime<Local>,
+    pub finished_at: DateTime<Local>,
+    pub accounts: Vec<AccountReport>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct StatusCounts {
+    success: usize,
+    info: usize,
+    fail: usize,
+    error: usize,
+    challenge: usize,
+}
+
+impl SignStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Success => "SUCCESS",
+            Self::Info => "INFO",
+            Self::Fail => "FAIL",
+            Self::Error => "ERROR",
+            Self::Challenge => "CHALLENGE",
+        }
+    }
+}
+
+impl SignRecord {
+    pub fn awards_text(&self) -> String {
+        self.awards
+            .iter()
+            .map(|award| format!("{}x{}", award.name, award.count))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    pub fn log_line(&self) -> String {
+        let awards = self.awards_text();
+        let awards = if awards.is_empty() {
+            String::new()
+        } else {
+            format!(" | 获得: {awards}")
+        };
+        format!(
+            "[{}] {}: {} - {}{}",
+            self.app_code.to_uppercase(),
+            self.display_name,
+            self.status.as_str(),
+            self.message,
+            awards
+        )
+    }
+
+    fn to_value(&self) -> Value {
+        object_from_pairs([
+            ("game", Value::String(self.app_code.clone())),
+            ("name", Value::String(self.display_name.clone())),
+            ("status", Value::String(self.status.as_str().to_string())),
+            ("message", Value::String(self.message.clone())),
+            (
+                "awards",
+                Value::Array(
+                    self.awards
+                        .iter()
+                        .map(|award| {
+                            object_from_pairs([
+                                ("name", Value::String(award.name.clone())),
+                                ("count", Value::String(award.count.clone())),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+            ("awards_text", Value::String(self.awards_text())),
+            (
+                "challenge",
+                self.challenge
+                    .as_ref()
+                    .map(Challenge::to_value)
+                    .unwrap_or(Value::Null),
+            ),
+        ])
+    }
+}
+
+impl AccountReport {
+    pub fn new(index: usize, label: impl Into<String>) -> Self {
+        let now = Local::now();
+        Self {
+            index,
+            label: label.into(),
+            success: true,
+            token_health: None,
+            credential_kind: "鹰角通行证 Token",
+            records: Vec::new(),
+            error: None,
+            challenge: None,
+            events: Vec::new(),
+            logs: Vec::new(),
+            started_at: now,
+            finished_at: now,
+        }
+    }
+
+    /// Selectors in notifier config name an account by label or by its
+    /// 1-based position among all loaded accounts: `SKYLAND_TOKEN` entries
+    /// first, then `SKYLAND_CRED`, then the vault.
+    pub fn matches(&self, selector: &str) -> bool {
+        let selector = selector.trim();
+        selector == self.label || selector == self.index.to_string()
+    }
+
+    pub fn token_expired(&self) -> bool {
+        self.token_health == Some(TokenHealth::Expired)
+    }
+
+    /// Recording also logs, so the line lands in whatever span is current.
+    pub fn push_record(&mut self, record: SignRecord) {
+        let line = record.log_line();
+        if matches!(
+            record.status,
+            SignStatus::Fail | SignStatus::Error | SignStatus::Challenge
+        ) {
+            self.success = false;
+            warn!(status = record.status.as_str(), "{line}");
+        } else {
+            info!(status = record.status.as_str(), "{line}");
+        }
+        self.logs.push(line);
+        self.records.push(record);
+    }
+
+    pub fn push_event(&mut self, event: String) {
+        info!("{event}");
+        self.logs.push(event.clone());
+        self.events.push(event);
+    }
+
+    pub fn fail(&mut self, log: String) {
+        error!("{log}");
+        self.success = false;
+        self.logs.push(log.clone());
+        self.error = Some(log);
+    }
+
+    pub fn finish(&mut self) {
+        self.finished_at = Local::now();
+    }
+
+    fn counts(&self) -> StatusCounts {
+        let mut counts = StatusCounts::default();
+        for record in &self.records {
+            match record.status {
+                SignStatus::Success => counts.success += 1,
+                SignStatus::Info => counts.info += 1,
+                SignStatus::Fail => counts.fail += 1,
+                SignStatus::Error => counts.error += 1,
+                SignStatus::Challenge => counts.challenge += 1,
+            }
+        }
+        counts
+    }
+
+    fn to_value(&self) -> Value {
+        object_from_pairs([
+            ("index", Value::Number(Number::from(self.index))),
+            ("label", Value::String(self.label.clone())),
+            ("success", Value::Bool(self.success)),
+            ("token_expired", Value::Bool(self.token_expired())),
+            (
+                "token_health",
+                Value::String(
+                    self.token_health
+                        .map(TokenHealth::as_str)
+                        .unwrap_or("UNKNOWN")
+                        .to_string(),
+                ),
+            ),
+            (
+                "error",
+                self.error.clone().map(Value::String).unwrap_or(Value::Null),
+            ),
+            (
+                "challenge",
+                self.challenge
+                    .as_ref()
+                    .map(Challenge::to_value)
+                    .unwrap_or(Value::Null),
+            ),
+            (
+                "events",
+                Value::Array(self.events.iter().cloned().map(Value::String).collect()),
+            ),
+            ("counts", self.counts().to_value()),
+            (
+                "characters",
+                Value::Array(self.records.iter().map(SignRecord::to_value).collect()),
+            ),
+            (
+                "logs",
+                Value::Array(self.logs.iter().cloned().map(Value::String).collect()),
+            ),
+            ("started_at", time_value(&self.started_at)),
+            ("finished_at", time_value(&self.finished_at)),
+            (
+                "duration",
+                duration_value(&self.started_at, &self.finished_at),
+            ),
+        ])
+    }
+}
+
+impl RunReport {
+    pub fn new() -> Self {
+        let now = Local::now();
+        Self {
+            started_at: now,
+            finished_at: now,
+            accounts: Vec::new(),
+        }
+    }
+
+    /// Template context for the given subset of accounts; run-wide timing is
+    /// kept, counts only cover the accounts a target actually receives.
+    pub fn context(&self, title: &str, accounts: &[&AccountReport]) -> Value {
+        let mut counts = StatusCounts::default();
+        for account in accounts {
+            counts.add(account.counts());
+        }
+
+        object_from_pairs([
+            ("title", Value::String(title.to_string())),
+            (
+                "success",
+                Value::Bool(accounts.iter().all(|account| account.success)),
+            ),
+            ("account_count", Value::Number(Number::from(accounts.len()))),
+            ("counts", counts.to_value()),
+            (
+                "accounts",
+                Value::Array(accounts.iter().map(|account| account.to_value()).collect()),
+            ),
+            ("started_at", time_value(&self.started_at)),
+            ("finished_at", time_value(&self.finished_at)),
+            (
+                "duration",
+                duration_value(&self.started_at, &self.finished_at),
+            ),
+        ])
+    }
+}
+
+impl StatusCounts {
+    fn add(&mut self, other: Self) {
+        self.success += other.success;
+        self.info += other.info;
+        self.fail += other.fail;
+        self.error += other.error;
+        self.challenge += other.challenge;
+    }
+
+    fn to_value(self) -> Value {
+        object_from_pairs([
+            ("success", Value::Number(Number::from(self.success))),
+            ("info", Value::Number(Number::from(self.info))),
+            ("fail", Value::Number(Number::from(self.fail))),
+            ("error", Value::Number(Number::from(self.error))),
+            ("challenge", Value::Number(Number::from(self.challenge))),
+            (
+                "total",
+                Value::Number(Number::from(
+                    self.success + self.info + self.fail + self.error + self.challenge,
+                )),
+            ),
+        ])
+    }
+}
+
+fn time_value(time: &DateTime<Local>) -> Value {
+    Value::String(time.format(TIME_FORMAT).to_string())
+}
+
+fn duration_value(start: &DateTime<Local>, end: &DateTime<Local>) -> Value {
+    let millis = (*end - *start).num_milliseconds().max(0);
+    Value::String(format!("{:.1}s", millis as f64 / 1000.0))
+}
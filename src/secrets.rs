@@ -0,0 +1,152 @@
+// Copyright 2026 YuzakiKokuban
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Where secret values come from. Besides literal values, any secret can be
+//! written as `file:/path`, `exec:command` (stdout of a shell command) or
+//! `stdin:` (everything piped into the process, read once), and every secret
+//! env var `NAME` can instead be given as `NAME_FILE=/path`.
+
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result, bail};
+use tracing::info;
+
+use crate::redact::register;
+use crate::utils::{mask_token, shell_command};
+
+static STDIN: OnceLock<String> = OnceLock::new();
+
+/// Reads `name`, resolving a source prefix in its value, or falls back to the
+/// file named by `name_FILE`. Empty values count as unset.
+pub fn secret_env(name: &str) -> Result<Option<String>> {
+    secret_var(name, |name| env::var(name).ok())
+}
+
+/// `secret_env` over any variable lookup.
+fn secret_var(name: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<Option<String>> {
+    if let Some(value) = non_empty(lookup(name)) {
+        return resolve_secret(&value, name).map(Some);
+    }
+
+    let file_var = format!("{name}_FILE");
+    match non_empty(lookup(&file_var)) {
+        Some(path) => {
+            let value = read_file(&path)?;
+            register(&value);
+            info!("Loaded {name} from {file_var} ({})", mask_token(&value));
+            Ok(non_empty(Some(value)))
+        }
+        None => Ok(None),
+    }
+}
+
+pub fn is_secret_source(value: &str) -> bool {
+    let value = value.trim();
+    value.starts_with("file:") || value.starts_with("exec:") || value == "stdin:" || value == "-"
+}
+
+/// Returns `value` unchanged unless it names a source; `what` identifies the
+/// secret in log lines, which only ever show the masked result. Either way
+/// the value is registered for redaction.
+pub fn resolve_secret(value: &str, what: &str) -> Result<String> {
+    let value = value.trim();
+    let (source, resolved) = if let Some(path) = value.strip_prefix("file:") {
+        (format!("file {path}"), read_file(path)?)
+    } else if let Some(command) = value.strip_prefix("exec:") {
+        ("command".to_string(), run_command(command, what)?)
+    } else if value == "stdin:" || value == "-" {
+        ("stdin".to_string(), read_stdin()?)
+    } else {
+        register(value);
+        return Ok(value.to_string());
+    };
+
+    if resolved.is_empty() {
+        bail!("{what} from {source} is empty");
+    }
+    register(&resolved);
+    info!("Loaded {what} from {source} ({})", mask_token(&resolved));
+    Ok(resolved)
+}
+
+fn read_file(path: &str) -> Result<String> {
+    let path = path.trim();
+    Ok(fs::read_to_string(path)
+        .with_context(|| format!("failed to read secret file {path}"))?
+        .trim()
+        .to_string())
+}
+
+/// Output of the command is never included in errors, since a failing
+/// password manager may still print part of a secret.
+fn run_command(command: &str, what: &str) -> Result<String> {
+    let output = shell_command(command)
+        .output()
+        .with_context(|| format!("failed to run secret command for {what}"))?;
+
+    if !output.status.success() {
+        bail!("secret command for {what} exited with {}", output.status);
+    }
+    String::from_utf8(output.stdout)
+        .map(|stdout| stdout.trim().to_string())
+        .with_context(|| format!("secret command for {what} printed non-UTF-8 output"))
+}
+
+fn read_stdin() -> Result<String> {
+    if let Some(value) = STDIN.get() {
+        return Ok(value.clone());
+    }
+
+    let mut buffer = String::new();
+    io::stdin()
+        .read_to_string(&mut buffer)
+        .context("failed to read secret from stdin")?;
+    Ok(STDIN.get_or_init(|| buffer.trim().to_string()).clone())
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_file_exec_and_file_env_sources() {
+        let path = env::temp_dir().join(format!("skas-secret-{}", std::process::id()));
+        fs::write(&path, "  token-from-file-0001\n").unwrap();
+        let file_source = format!("file:{}", path.display());
+
+        assert_eq!(
+            resolve_secret(&file_source, "TEST").unwrap(),
+            "token-from-file-0001"
+        );
+        assert_eq!(
+            resolve_secret("exec:echo token-from-exec-0002", "TEST").unwrap(),
+            "token-from-exec-0002"
+        );
+        assert!(resolve_secret("exec:exit 3", "TEST").is_err());
+        assert!(resolve_secret("exec:true", "TEST").is_err());
+        assert_eq!(resolve_secret(" plain ", "TEST").unwrap(), "plain");
+
+        let file_env =
+            |name: &str| (name == "SKYLAND_TOKEN_FILE").then(|| path.display().to_string());
+        assert_eq!(
+            secret_var("SKYLAND_TOKEN", file_env).unwrap().as_deref(),
+            Some("token-from-file-0001")
+        );
+        let direct_env = |name: &str| (name == "SKYLAND_TOKEN").then(|| file_source.clone());
+        assert_eq!(
+            secret_var("SKYLAND_TOKEN", direct_env).unwrap().as_deref(),
+            Some("token-from-file-0001")
+        );
+        assert_eq!(secret_var("SKYLAND_TOKEN", |_| None).unwrap(), None);
+        fs::remove_file(&path).unwrap();
+    }
+}
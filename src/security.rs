@@ -1,196 +1,188 @@
 // Copyright 2026 YuzakiKokuban
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use std::io::Write;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::fs;
+use std::io::{Read, Write};
 
 use aes::Aes128;
-use anyhow::{Context, Result, anyhow};
+use anyhow::{Context, Result, anyhow, bail};
 use base64::Engine;
 use base64::engine::general_purpose::STANDARD;
-use cbc::Encryptor as CbcEncryptor;
-use chrono::Local;
+use cbc::{Decryptor as CbcDecryptor, Encryptor as CbcEncryptor};
 use ecb::cipher::block_padding::NoPadding;
-use ecb::cipher::{BlockModeEncrypt, KeyInit, KeyIvInit};
+use ecb::cipher::{BlockModeDecrypt, BlockModeEncrypt, KeyInit, KeyIvInit};
 use des::TdesEde3;
-use ecb::Encryptor as EcbEncryptor;
+use ecb::{Decryptor as EcbDecryptor, Encryptor as EcbEncryptor};
 use flate2::Compression;
 use flate2::GzBuilder;
+use flate2::read::GzDecoder;
 use reqwest::blocking::Client;
-use rsa::rand_core::OsRng;
 use rsa::{RsaPublicKey, pkcs1v15::Pkcs1v15Encrypt, pkcs8::DecodePublicKey};
 use serde_json::{Map, Number, Value};
-use uuid::Uuid;
 
+use crate::config::non_empty_env;
+use crate::fingerprint::DeviceProfile;
+use crate::logging::send_json;
+use crate::metrics;
+use crate::system::{Clock, RandCompat, RandomSource};
 use crate::utils::{md5_hex, object_from_pairs, python_json_string};
 
 const DEVICES_INFO_URL: &str = "https://fp-it.portal101.cn/deviceprofile/v4";
-const SM_ORGANIZATION: &str = "UWXspnCCJN4sfYlNfqps";
-const SM_APP_ID: &str = "default";
-const SM_PUBLIC_KEY: &str = "MIGfMA0GCSqGSIb3DQEBAQUAA4GNADCBiQKBgQCmxMNr7n8ZeT0tE1R9j/mPixoinPkeM+k4VGIn/s0k7N5rJAfnZ0eMER+QhwFvshzo0LNmeUkpR8uIlU/GEVr8mN28sKmwd2gpygqj0ePnBmOW4v0ZVwbSYK+izkhVFk2V/doLoMbWy6b+UnA8mkjvg0iYWRByfRsK2gdl7llqCwIDAQAB";
-
 type TdesEcbEnc = EcbEncryptor<TdesEde3>;
+type TdesEcbDec = EcbDecryptor<TdesEde3>;
 type AesCbcEnc = CbcEncryptor<Aes128>;
+type AesCbcDec = CbcDecryptor<Aes128>;
+
+const AES_IV: &[u8; 16] = b"0102030405060708";
+
+/// The Shumei web SDK 3.0.0 table, used unless `SHUMEI_SDK_FILE` names
+/// another.
+const EMBEDDED_SDK: &str = include_str!("shumei_sdk.json");
+
+/// The only layout of the SDK file understood so far.
+const SDK_SCHEMA: i64 = 1;
+
+/// What changes when Shumei ships a new web SDK: account identifiers, the
+/// RSA key for `ep`, protocol versions and the field obfuscation table.
+pub struct ShumeiSdk {
+    pub sdkver: String,
+    pub sub_version: String,
+    pub protocol: i64,
+    pub organization: String,
+    pub app_id: String,
+    public_key: RsaPublicKey,
+    /// Field name to rule, in file order.
+    rules: Vec<(String, DesRule)>,
+}
 
-#[derive(Clone, Copy)]
 struct DesRule {
-    is_encrypt: bool,
-    key: Option<&'static str>,
-    obfuscated_name: &'static str,
+    /// The 3DES key; `None` for fields that are only renamed.
+    key: Option<String>,
+    obfuscated_name: String,
+}
+
+impl Default for ShumeiSdk {
+    fn default() -> Self {
+        ShumeiSdk::parse(EMBEDDED_SDK).expect("embedded Shumei SDK table is valid")
+    }
 }
 
-fn des_rule(key: &str) -> Option<DesRule> {
-    Some(match key {
-        "appId" => DesRule {
-            is_encrypt: true,
-            key: Some("uy7mzc4h"),
-            obfuscated_name: "xx",
-        },
-        "box" => DesRule {
-            is_encrypt: false,
-            key: None,
-            obfuscated_name: "jf",
-        },
-        "canvas" => DesRule {
-            is_encrypt: true,
-            key: Some("snrn887t"),
-            obfuscated_name: "yk",
-        },
-        "clientSize" => DesRule {
-            is_encrypt: true,
-            key: Some("cpmjjgsu"),
-            obfuscated_name: "zx",
-        },
-        "organization" => DesRule {
-            is_encrypt: true,
-            key: Some("78moqjfc"),
-            obfuscated_name: "dp",
-        },
-        "os" => DesRule {
-            is_encrypt: true,
-            key: Some("je6vk6t4"),
-            obfuscated_name: "pj",
-        },
-        "platform" => DesRule {
-            is_encrypt: true,
-            key: Some("pakxhcd2"),
-            obfuscated_name: "gm",
-        },
-        "plugins" => DesRule {
-            is_encrypt: true,
-            key: Some("v51m3pzl"),
-            obfuscated_name: "kq",
-        },
-        "pmf" => DesRule {
-            is_encrypt: true,
-            key: Some("2mdeslu3"),
-            obfuscated_name: "vw",
-        },
-        "protocol" => DesRule {
-            is_encrypt: false,
-            key: None,
-            obfuscated_name: "protocol",
-        },
-        "referer" => DesRule {
-            is_encrypt: true,
-            key: Some("y7bmrjlc"),
-            obfuscated_name: "ab",
-        },
-        "res" => DesRule {
-            is_encrypt: true,
-            key: Some("whxqm2a7"),
-            obfuscated_name: "hf",
-        },
-        "rtype" => DesRule {
-            is_encrypt: true,
-            key: Some("x8o2h2bl"),
-            obfuscated_name: "lo",
-        },
-        "sdkver" => DesRule {
-            is_encrypt: true,
-            key: Some("9q3dcxp2"),
-            obfuscated_name: "sc",
-        },
-        "status" => DesRule {
-            is_encrypt: true,
-            key: Some("2jbrxxw4"),
-            obfuscated_name: "an",
-        },
-        "subVersion" => DesRule {
-            is_encrypt: true,
-            key: Some("eo3i2puh"),
-            obfuscated_name: "ns",
-        },
-        "svm" => DesRule {
-            is_encrypt: true,
-            key: Some("fzj3kaeh"),
-            obfuscated_name: "qr",
-        },
-        "time" => DesRule {
-            is_encrypt: true,
-            key: Some("q2t3odsk"),
-            obfuscated_name: "nb",
-        },
-        "timezone" => DesRule {
-            is_encrypt: true,
-            key: Some("1uv05lj5"),
-            obfuscated_name: "as",
-        },
-        "tn" => DesRule {
-            is_encrypt: true,
-            key: Some("x9nzj1bp"),
-            obfuscated_name: "py",
-        },
-        "trees" => DesRule {
-            is_encrypt: true,
-            key: Some("acfs0xo4"),
-            obfuscated_name: "pi",
-        },
-        "ua" => DesRule {
-            is_encrypt: true,
-            key: Some("k92crp1t"),
-            obfuscated_name: "bj",
-        },
-        "url" => DesRule {
-            is_encrypt: true,
-            key: Some("y95hjkoo"),
-            obfuscated_name: "cf",
-        },
-        "version" => DesRule {
-            is_encrypt: false,
-            key: None,
-            obfuscated_name: "version",
-        },
-        "vpw" => DesRule {
-            is_encrypt: true,
-            key: Some("r9924ab5"),
-            obfuscated_name: "ca",
-        },
-        _ => return None,
+impl ShumeiSdk {
+    /// Parses and validates an SDK file.
+    pub fn parse(raw: &str) -> Result<Self> {
+        let parsed = serde_json::from_str::<Value>(raw).context("not valid JSON")?;
+        let fields = parsed.as_object().context("expected a JSON object")?;
+        let string_field = |key: &str| -> Result<String> {
+            fields
+                .get(key)
+                .and_then(Value::as_str)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+                .with_context(|| format!("{key} must be a non-empty string"))
+        };
+
+        let schema = fields.get("schema").and_then(Value::as_i64);
+        if schema != Some(SDK_SCHEMA) {
+            bail!("unsupported schema {schema:?}, expected {SDK_SCHEMA}");
+        }
+        let protocol = fields
+            .get("protocol")
+            .and_then(Value::as_i64)
+            .filter(|protocol| *protocol > 0)
+            .context("protocol must be a positive integer")?;
+        let public_key_der = STANDARD
+            .decode(string_field("publicKey")?)
+            .context("publicKey is not base64")?;
+        let public_key = RsaPublicKey::from_public_key_der(&public_key_der)
+            .context("publicKey is not an RSA public key")?;
+
+        let entries = fields
+            .get("rules")
+            .and_then(Value::as_object)
+            .filter(|rules| !rules.is_empty())
+            .context("rules must be a non-empty object")?;
+        let mut rules = Vec::<(String, DesRule)>::with_capacity(entries.len());
+        for (name, rule) in entries {
+            let rule = parse_rule(rule).with_context(|| format!("invalid rule {name}"))?;
+            if let Some((other, _)) = rules
+                .iter()
+                .find(|(_, known)| known.obfuscated_name == rule.obfuscated_name)
+            {
+                bail!(
+                    "rules {other} and {name} share the obfuscated name {}",
+                    rule.obfuscated_name
+                );
+            }
+            rules.push((name.clone(), rule));
+        }
+
+        Ok(ShumeiSdk {
+            sdkver: string_field("sdkver")?,
+            sub_version: string_field("subVersion")?,
+            protocol,
+            organization: string_field("organization")?,
+            app_id: string_field("appId")?,
+            public_key,
+            rules,
+        })
+    }
+
+    fn rule(&self, field: &str) -> Option<&DesRule> {
+        self.rules
+            .iter()
+            .find(|(name, _)| name == field)
+            .map(|(_, rule)| rule)
+    }
+
+    /// The original field name behind an obfuscated one, if any.
+    pub fn original_name(&self, obfuscated: &str) -> Option<&str> {
+        self.rules
+            .iter()
+            .find(|(_, rule)| rule.obfuscated_name == obfuscated)
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// Accepts `is_encrypt` as a bool or 0/1, as dumped from the SDK.
+fn parse_rule(rule: &Value) -> Result<DesRule> {
+    let rule = rule.as_object().context("expected a JSON object")?;
+    let is_encrypt = match rule.get("is_encrypt") {
+        Some(Value::Bool(flag)) => *flag,
+        Some(Value::Number(flag)) if flag.as_i64() == Some(0) => false,
+        Some(Value::Number(flag)) if flag.as_i64() == Some(1) => true,
+        _ => bail!("is_encrypt must be true, false, 0 or 1"),
+    };
+    let obfuscated_name = rule
+        .get("obfuscated_name")
+        .and_then(Value::as_str)
+        .filter(|name| !name.is_empty())
+        .context("obfuscated_name must be a non-empty string")?
+        .to_string();
+    let key = if is_encrypt {
+        let key = rule
+            .get("key")
+            .and_then(Value::as_str)
+            .context("encrypted rules need a key")?;
+        if key.len() != 8 || !key.is_ascii() {
+            bail!("key must be 8 ASCII characters");
+        }
+        Some(key.to_string())
+    } else {
+        None
+    };
+    Ok(DesRule {
+        key,
+        obfuscated_name,
     })
 }
 
-fn browser_env() -> Value {
-    object_from_pairs([
-        (
-            "plugins",
-            Value::String("MicrosoftEdgePDFPluginPortableDocumentFormatinternal-pdf-viewer1,MicrosoftEdgePDFViewermhjfbmdgcfjbbpaeojofohoefgiehjai1".to_string()),
-        ),
-        (
-            "ua",
-            Value::String(
-                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/129.0.0.0 Safari/537.36 Edg/129.0.0.0".to_string(),
-            ),
-        ),
-        ("canvas", Value::String("259ffe69".to_string())),
-        ("timezone", Value::Number(Number::from(-480))),
-        ("platform", Value::String("Win32".to_string())),
-        ("url", Value::String("https://www.skland.com/".to_string())),
-        ("referer", Value::String(String::new())),
-        ("res", Value::String("1920_1080_24_1.25".to_string())),
-        ("clientSize", Value::String("0_0_1080_1920_1920_1080_1920_1080".to_string())),
-        ("status", Value::String("0011".to_string())),
-    ])
+/// Reads the SDK table from `SHUMEI_SDK_FILE`, or the embedded default.
+pub fn load_shumei_sdk() -> Result<ShumeiSdk> {
+    let Some(path) = non_empty_env("SHUMEI_SDK_FILE") else {
+        return Ok(ShumeiSdk::default());
+    };
+    let raw = fs::read_to_string(&path).with_context(|| format!("failed to read {path}"))?;
+    ShumeiSdk::parse(&raw).with_context(|| format!("invalid Shumei SDK file {path}"))
 }
 
 fn triple_des_encrypt_base64(value: &str, key: &str) -> Result<String> {
@@ -214,6 +206,7 @@ fn triple_des_encrypt_base64(value: &str, key: &str) -> Result<String> {
     Ok(STANDARD.encode(encrypted))
 }
 
+#[allow(clippy::manual_is_multiple_of)]
 fn aes_encrypt_hex(value: &[u8], key: &[u8]) -> Result<String> {
     let mut data = value.to_vec();
     data.push(0_u8);
@@ -222,14 +215,40 @@ fn aes_encrypt_hex(value: &[u8], key: &[u8]) -> Result<String> {
     }
 
     let msg_len = data.len();
-    let iv = b"0102030405060708";
-    let cipher = AesCbcEnc::new_from_slices(key, iv).context("failed to build AES cipher")?;
+    let cipher = AesCbcEnc::new_from_slices(key, AES_IV).context("failed to build AES cipher")?;
     let encrypted = cipher
         .encrypt_padded::<NoPadding>(&mut data, msg_len)
         .map_err(|_| anyhow!("failed to encrypt AES payload"))?;
     Ok(hex::encode(encrypted))
 }
 
+/// Reverses `triple_des_encrypt_base64`, dropping the zero padding.
+fn triple_des_decrypt_base64(value: &str, key: &str) -> Result<String> {
+    let expanded_key = key.repeat(3);
+    let mut data = STANDARD.decode(value).context("invalid 3DES base64")?;
+    let cipher = TdesEcbDec::new_from_slice(expanded_key.as_bytes())
+        .context("failed to build 3DES cipher")?;
+    let decrypted = cipher
+        .decrypt_padded::<NoPadding>(&mut data)
+        .map_err(|_| anyhow!("failed to decrypt 3DES value"))?;
+    let text = String::from_utf8_lossy(decrypted);
+    Ok(text.trim_end_matches('\0').to_string())
+}
+
+/// Reverses `aes_encrypt_hex`, dropping the zero padding.
+fn aes_decrypt_hex(value: &str, key: &[u8]) -> Result<Vec<u8>> {
+    let mut data = hex::decode(value.trim()).context("data is not hex")?;
+    let cipher = AesCbcDec::new_from_slices(key, AES_IV).context("failed to build AES cipher")?;
+    let decrypted = cipher
+        .decrypt_padded::<NoPadding>(&mut data)
+        .map_err(|_| anyhow!("data is not a whole number of AES blocks"))?;
+    let len = decrypted
+        .iter()
+        .rposition(|byte| *byte != 0)
+        .map_or(0, |last| last + 1);
+    Ok(decrypted[..len].to_vec())
+}
+
 fn gzip_base64(value: &Value) -> Result<Vec<u8>> {
     let json = python_json_string(value);
     let mut encoder = GzBuilder::new()
@@ -277,21 +296,20 @@ fn get_tn_map(map: &Map<String, Value>) -> String {
     result
 }
 
-fn get_smid() -> String {
-    let time_part = Local::now().format("%Y%m%d%H%M%S").to_string();
-    let uid = Uuid::new_v4().to_string();
+fn get_smid(clock: &dyn Clock, rng: &mut dyn RandomSource) -> String {
+    let time_part = clock.now().format("%Y%m%d%H%M%S").to_string();
+    let uid = rng.uuid_v4().to_string();
     let base = format!("{time_part}{}00", md5_hex(uid));
     let suffix = md5_hex(format!("smsk_web_{base}"));
     format!("{base}{}0", &suffix[..14])
 }
 
-fn des_transform(map: &Map<String, Value>) -> Result<Value> {
+fn des_transform(sdk: &ShumeiSdk, map: &Map<String, Value>) -> Result<Value> {
     let mut result = Map::new();
 
     for (key, value) in map {
-        if let Some(rule) = des_rule(key) {
-            let transformed = if rule.is_encrypt {
-                let key = rule.key.context("missing DES rule key")?;
+        if let Some(rule) = sdk.rule(key) {
+            let transformed = if let Some(key) = &rule.key {
                 let raw = match value {
                     Value::String(value) => value.clone(),
                     other => python_json_string(other).trim_matches('"').to_string(),
@@ -300,7 +318,7 @@ fn des_transform(map: &Map<String, Value>) -> Result<Value> {
             } else {
                 value.clone()
             };
-            result.insert(rule.obfuscated_name.to_string(), transformed);
+            result.insert(rule.obfuscated_name.clone(), transformed);
         } else {
             result.insert(key.clone(), value.clone());
         }
@@ -309,34 +327,137 @@ fn des_transform(map: &Map<String, Value>) -> Result<Value> {
     Ok(Value::Object(result))
 }
 
-pub fn get_d_id(http: &Client) -> Result<String> {
-    let uid = Uuid::new_v4().to_string().into_bytes();
+/// Maps obfuscated names back to the original fields and decrypts their
+/// values. Encrypted numbers come back as strings, as they were sent.
+pub fn undo_des_transform(sdk: &ShumeiSdk, map: &Map<String, Value>) -> Result<Map<String, Value>> {
+    let mut result = Map::new();
+    for (key, value) in map {
+        let original = sdk
+            .original_name(key)
+            .and_then(|field| sdk.rule(field).map(|rule| (field, rule)));
+        match original {
+            Some((field, rule)) => {
+                let value = match &rule.key {
+                    Some(key) => {
+                        let raw = value
+                            .as_str()
+                            .with_context(|| format!("{field} is not a string"))?;
+                        let plain = triple_des_decrypt_base64(raw, key)
+                            .with_context(|| format!("failed to decrypt {field}"))?;
+                        Value::String(plain)
+                    }
+                    None => value.clone(),
+                };
+                result.insert(field.to_string(), value);
+            }
+            None => {
+                result.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Decrypts the `data` field of a device-profile request with the `pri_id`
+/// it was encrypted under, giving the DES-obfuscated map.
+pub fn decrypt_device_profile_data(data: &str, pri_id: &str) -> Result<Map<String, Value>> {
+    let compressed = aes_decrypt_hex(data, pri_id.as_bytes())?;
+    let gzip = STANDARD
+        .decode(&compressed)
+        .context("decrypted data is not base64; wrong pri_id?")?;
+    let mut json = String::new();
+    GzDecoder::new(gzip.as_slice())
+        .read_to_string(&mut json)
+        .context("decrypted data is not gzip")?;
+    match serde_json::from_str(&json).context("decrypted data is not JSON")? {
+        Value::Object(map) => Ok(map),
+        _ => bail!("decrypted data is not a JSON object"),
+    }
+}
+
+/// Requests a `dId` for `device`. Time and randomness come from `clock` and
+/// `rng`; see `device_profile_body`.
+pub fn get_d_id(
+    http: &Client,
+    sdk: &ShumeiSdk,
+    device: &DeviceProfile,
+    clock: &dyn Clock,
+    rng: &mut dyn RandomSource,
+) -> Result<String> {
+    let body = device_profile_body(sdk, device, clock, rng)?;
+    let response_json = send_json(
+        http.post(DEVICES_INFO_URL)
+            .header("Content-Type", "application/json")
+            .body(python_json_string(&body)),
+        "device profile",
+    )
+    .inspect_err(|_| metrics::record_device_profile_failure())?;
+    if response_json.get("code").and_then(Value::as_i64) != Some(1100) {
+        metrics::record_device_profile_failure();
+        return Ok(String::new());
+    }
+
+    let device_id = response_json
+        .get("detail")
+        .and_then(Value::as_object)
+        .and_then(|detail| detail.get("deviceId"))
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+
+    Ok(format!("B{device_id}"))
+}
+
+/// Every stage of a device-profile request, for `skas device inspect`.
+pub struct DeviceProfilePayload {
+    /// The fingerprint and SDK fields before `tn` is added.
+    pub target: Map<String, Value>,
+    pub tn_input: String,
+    pub tn: String,
+    pub obfuscated: Map<String, Value>,
+    /// Base64 of the gzipped obfuscated map, the AES plaintext.
+    pub compressed: Vec<u8>,
+    /// The AES key, sent RSA-encrypted as `ep`.
+    pub pri_id: String,
+    pub body: Value,
+}
+
+fn device_profile_body(
+    sdk: &ShumeiSdk,
+    device: &DeviceProfile,
+    clock: &dyn Clock,
+    rng: &mut dyn RandomSource,
+) -> Result<Value> {
+    Ok(build_device_profile(sdk, device, clock, rng)?.body)
+}
+
+/// Builds the encrypted Shumei payload without sending it. A pure function
+/// of its inputs: the same clock and random bytes always give the same body.
+pub fn build_device_profile(
+    sdk: &ShumeiSdk,
+    device: &DeviceProfile,
+    clock: &dyn Clock,
+    rng: &mut dyn RandomSource,
+) -> Result<DeviceProfilePayload> {
+    let uid = rng.uuid_v4().to_string().into_bytes();
     let pri_id = md5_hex(&uid)[..16].to_string();
 
-    let public_key_der = STANDARD
-        .decode(SM_PUBLIC_KEY)
-        .context("invalid RSA public key")?;
-    let public_key = RsaPublicKey::from_public_key_der(&public_key_der)
-        .context("failed to parse RSA public key")?;
-    let encrypted_uid = public_key
-        .encrypt(&mut OsRng, Pkcs1v15Encrypt, &uid)
+    let encrypted_uid = sdk
+        .public_key
+        .encrypt(&mut RandCompat(&mut *rng), Pkcs1v15Encrypt, &uid)
         .context("failed to encrypt device payload")?;
     let ep = STANDARD.encode(encrypted_uid);
 
-    let current_time = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .context("system clock is before unix epoch")?
-        .as_millis() as i64;
+    let current_time = clock.unix_millis();
 
-    let mut browser = match browser_env() {
+    let mut browser = match device.browser_env() {
         Value::Object(map) => map,
         _ => Map::new(),
     };
-    browser.insert("vpw".to_string(), Value::String(Uuid::new_v4().to_string()));
+    browser.insert("vpw".to_string(), Value::String(rng.uuid_v4().to_string()));
     browser.insert("svm".to_string(), Value::Number(Number::from(current_time)));
     browser.insert(
         "trees".to_string(),
-        Value::String(Uuid::new_v4().to_string()),
+        Value::String(rng.uuid_v4().to_string()),
     );
     browser.insert("pmf".to_string(), Value::Number(Number::from(current_time)));
 
@@ -344,58 +465,224 @@ pub fn get_d_id(http: &Client) -> Result<String> {
     for (key, value) in browser {
         target.insert(key, value);
     }
-    target.insert("protocol".to_string(), Value::Number(Number::from(102)));
+    target.insert(
+        "protocol".to_string(),
+        Value::Number(Number::from(sdk.protocol)),
+    );
     target.insert(
         "organization".to_string(),
-        Value::String(SM_ORGANIZATION.to_string()),
+        Value::String(sdk.organization.clone()),
     );
-    target.insert("appId".to_string(), Value::String(SM_APP_ID.to_string()));
+    target.insert("appId".to_string(), Value::String(sdk.app_id.clone()));
     target.insert("os".to_string(), Value::String("web".to_string()));
-    target.insert("version".to_string(), Value::String("3.0.0".to_string()));
-    target.insert("sdkver".to_string(), Value::String("3.0.0".to_string()));
+    target.insert("version".to_string(), Value::String(sdk.sdkver.clone()));
+    target.insert("sdkver".to_string(), Value::String(sdk.sdkver.clone()));
     target.insert("box".to_string(), Value::String(String::new()));
     target.insert("rtype".to_string(), Value::String("all".to_string()));
-    target.insert("smid".to_string(), Value::String(get_smid()));
-    target.insert("subVersion".to_string(), Value::String("1.0.0".to_string()));
+    target.insert("smid".to_string(), Value::String(get_smid(clock, rng)));
+    target.insert(
+        "subVersion".to_string(),
+        Value::String(sdk.sub_version.clone()),
+    );
     target.insert("time".to_string(), Value::Number(Number::from(0)));
 
-    let tn = md5_hex(get_tn_map(&target));
-    target.insert("tn".to_string(), Value::String(tn));
+    let tn_input = get_tn_map(&target);
+    let tn = md5_hex(&tn_input);
+    let mut with_tn = target.clone();
+    with_tn.insert("tn".to_string(), Value::String(tn.clone()));
 
-    let transformed = des_transform(&target)?;
-    let compressed = gzip_base64(&transformed)?;
+    let obfuscated = match des_transform(sdk, &with_tn)? {
+        Value::Object(map) => map,
+        _ => Map::new(),
+    };
+    let compressed = gzip_base64(&Value::Object(obfuscated.clone()))?;
     let data = aes_encrypt_hex(&compressed, pri_id.as_bytes())?;
 
     let body = object_from_pairs([
-        ("appId", Value::String(SM_APP_ID.to_string())),
+        ("appId", Value::String(sdk.app_id.clone())),
         ("compress", Value::Number(Number::from(2))),
         ("data", Value::String(data)),
         ("encode", Value::Number(Number::from(5))),
         ("ep", Value::String(ep)),
-        ("organization", Value::String(SM_ORGANIZATION.to_string())),
+        ("organization", Value::String(sdk.organization.clone())),
         ("os", Value::String("web".to_string())),
     ]);
+    Ok(DeviceProfilePayload {
+        target,
+        tn_input,
+        tn,
+        obfuscated,
+        compressed,
+        pri_id,
+        body,
+    })
+}
 
-    let response = http
-        .post(DEVICES_INFO_URL)
-        .header("Content-Type", "application/json")
-        .body(python_json_string(&body))
-        .send()
-        .context("failed to request device profile")?;
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
 
-    let response_json = response
-        .json::<Value>()
-        .context("invalid device profile response")?;
-    if response_json.get("code").and_then(Value::as_i64) != Some(1100) {
-        return Ok(String::new());
+    use super::*;
+    use crate::system::fixed::{CountingRandom, FixedClock};
+
+    /// 2026-01-02 03:04:05.678 UTC+8.
+    const NOW_MS: i64 = 1_767_294_245_678;
+
+    #[test]
+    fn smid_matches_golden_vector() {
+        let smid = get_smid(&FixedClock(NOW_MS), &mut CountingRandom(0));
+        assert_eq!(
+            smid,
+            "20260102030405f4a3c6ec751e59680380b1fea06a9a21008d035a4b15dd260"
+        );
     }
 
-    let device_id = response_json
-        .get("detail")
-        .and_then(Value::as_object)
-        .and_then(|detail| detail.get("deviceId"))
-        .and_then(Value::as_str)
-        .unwrap_or_default();
+    // The vectors below come from the reference Python implementation
+    // (pycryptodome, `json.dumps` and `gzip.compress`) on the same inputs.
+
+    #[test]
+    fn triple_des_matches_reference() {
+        assert_eq!(
+            triple_des_encrypt_base64("web", "je6vk6t4").unwrap(),
+            "qeDGcpUpK4Y="
+        );
+        assert_eq!(
+            triple_des_encrypt_base64("1920_1080_24_1.25", "whxqm2a7").unwrap(),
+            "5Ff2HmUkil1XYnjmjWlu/K55KTcmUZpP"
+        );
+        assert_eq!(triple_des_encrypt_base64("", "uy7mzc4h").unwrap(), "");
+    }
 
-    Ok(format!("B{device_id}"))
+    #[test]
+    fn aes_matches_reference() {
+        let key = b"0123456789abcdef";
+        assert_eq!(
+            aes_encrypt_hex(b"hello skland", key).unwrap(),
+            "4fd4b55d42f216415867e1b203c76796"
+        );
+        // 15 bytes plus the appended zero fill exactly one block.
+        assert_eq!(
+            aes_encrypt_hex(b"0123456789abcde", key).unwrap(),
+            "9e5258eb30929db438bdb16ad064caf3"
+        );
+    }
+
+    #[test]
+    fn json_and_gzip_match_reference() {
+        let value: Value =
+            serde_json::from_str(r#"{"b":"x","a":1,"c":{"z":true,"y":null}}"#).unwrap();
+        assert_eq!(
+            python_json_string(&value),
+            r#"{"b": "x", "a": 1, "c": {"z": true, "y": null}}"#
+        );
+
+        let ours = STANDARD.decode(gzip_base64(&value).unwrap()).unwrap();
+        let reference = STANDARD
+            .decode(
+                "H4sIAAAAAAAAA6tWSlKyUlCqUNJRUEoEsgyBdDKQrlaqApIlRaWpQIFKIDOvNCenthYAds3Gfi8AAAA=",
+            )
+            .unwrap();
+        // Same magic, method, flags, zero mtime and deflate stream; only the
+        // OS byte (offset 9) differs, 255 here and 3 from CPython's zlib.
+        assert_eq!(ours[..9], reference[..9]);
+        assert_eq!(ours[10..], reference[10..]);
+
+        let mut json = String::new();
+        flate2::read::GzDecoder::new(ours.as_slice())
+            .read_to_string(&mut json)
+            .unwrap();
+        assert_eq!(json, python_json_string(&value));
+    }
+
+    #[test]
+    fn tn_sorts_keys_and_scales_numbers() {
+        let value = serde_json::json!({"b": "x", "a": 12, "c": {"z": "q", "y": -3}, "d": "0"});
+        assert_eq!(get_tn_map(value.as_object().unwrap()), "120000x-30000q0");
+    }
+
+    #[test]
+    fn des_transform_renames_and_encrypts() {
+        let value = serde_json::json!({
+            "os": "web",
+            "res": "1920_1080_24_1.25",
+            "protocol": 102,
+            "timezone": -480,
+            "extra": "kept",
+        });
+        let transformed = des_transform(&ShumeiSdk::default(), value.as_object().unwrap()).unwrap();
+        assert_eq!(
+            python_json_string(&transformed),
+            r#"{"pj": "qeDGcpUpK4Y=", "hf": "5Ff2HmUkil1XYnjmjWlu/K55KTcmUZpP", "protocol": 102, "as": "ZiEGW6ynU0Q=", "extra": "kept"}"#
+        );
+    }
+
+    #[test]
+    fn captured_data_decrypts_back_to_target() {
+        let sdk = ShumeiSdk::default();
+        let payload = build_device_profile(
+            &sdk,
+            &DeviceProfile::default(),
+            &FixedClock(NOW_MS),
+            &mut CountingRandom(0),
+        )
+        .unwrap();
+        let data = payload.body["data"].as_str().unwrap();
+
+        let obfuscated = decrypt_device_profile_data(data, &payload.pri_id).unwrap();
+        assert_eq!(obfuscated, payload.obfuscated);
+        let fields = undo_des_transform(&sdk, &obfuscated).unwrap();
+        assert_eq!(fields["os"], "web");
+        assert_eq!(fields["svm"], NOW_MS.to_string());
+        assert_eq!(fields["protocol"], 102);
+        assert_eq!(fields["tn"], payload.tn.as_str());
+        assert!(decrypt_device_profile_data(data, "0000000000000000").is_err());
+    }
+
+    #[test]
+    fn sdk_file_is_validated() {
+        let sdk = ShumeiSdk::default();
+        assert_eq!(sdk.sdkver, "3.0.0");
+        assert_eq!(sdk.original_name("pj"), Some("os"));
+
+        let mut file = serde_json::from_str::<Value>(EMBEDDED_SDK).unwrap();
+        file["rules"]["ua"]["obfuscated_name"] = Value::from("pj");
+        let error = ShumeiSdk::parse(&file.to_string()).err().unwrap();
+        assert!(format!("{error:#}").contains("share the obfuscated name pj"));
+
+        let mut file = serde_json::from_str::<Value>(EMBEDDED_SDK).unwrap();
+        file["rules"]["os"]["key"] = Value::from("short");
+        let error = ShumeiSdk::parse(&file.to_string()).err().unwrap();
+        assert!(format!("{error:#}").contains("invalid rule os"));
+
+        let mut file = serde_json::from_str::<Value>(EMBEDDED_SDK).unwrap();
+        file["schema"] = Value::from(2);
+        assert!(ShumeiSdk::parse(&file.to_string()).is_err());
+    }
+
+    /// Pins every layer at once: tn hash, DES transform, gzip, AES-CBC and
+    /// the RSA-wrapped key. Any change to the payload shows up here.
+    #[test]
+    fn device_profile_body_matches_golden_vector() {
+        let body = device_profile_body(
+            &ShumeiSdk::default(),
+            &DeviceProfile::default(),
+            &FixedClock(NOW_MS),
+            &mut CountingRandom(0),
+        )
+        .unwrap();
+
+        assert_eq!(body["data"].as_str().unwrap().len(), 2304);
+        assert_eq!(
+            md5_hex(body["data"].as_str().unwrap()),
+            "a39b133a50a0c1a6274f182920fb9bf0"
+        );
+        assert_eq!(
+            md5_hex(body["ep"].as_str().unwrap()),
+            "e48cc8f8a675db2c74333d68cd80e184"
+        );
+        assert_eq!(
+            md5_hex(python_json_string(&body)),
+            "b72228b28639616411be23cecbb9b2f0"
+        );
+    }
 }
@@ -0,0 +1,67 @@
+// Copyright 2026 YuzakiKokuban
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use serde_json::{Map, Value};
+
+use crate::report::AccountReport;
+use crate::utils::{object_from_pairs, python_json_string};
+
+/// Outcome of the previous run per account label, kept in `NOTIFY_STATE_FILE`
+/// so the `changed` notify filter can compare against it. Without a state
+/// file every account counts as changed.
+pub struct RunState {
+    path: Option<PathBuf>,
+    previous: Map<String, Value>,
+}
+
+impl RunState {
+    pub fn load(path: Option<String>) -> Self {
+        let path = path.map(PathBuf::from);
+        let previous = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|raw| serde_json::from_str::<Value>(&raw).ok())
+            .and_then(|value| match value {
+                Value::Object(map) => Some(map),
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        Self { path, previous }
+    }
+
+    pub fn changed(&self, report: &AccountReport) -> bool {
+        let Some(previous) = self.previous.get(&report.label) else {
+            return true;
+        };
+        previous.get("success").and_then(Value::as_bool) != Some(report.success)
+            || previous.get("tokenExpired").and_then(Value::as_bool) != Some(report.token_expired())
+    }
+
+    pub fn save(&self, reports: &[AccountReport]) -> Result<()> {
+        let Some(path) = self.path.as_ref() else {
+            return Ok(());
+        };
+
+        let mut state = self.previous.clone();
+        let updated_at = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        for report in reports {
+            state.insert(
+                report.label.clone(),
+                object_from_pairs([
+                    ("success", Value::Bool(report.success)),
+                    ("tokenExpired", Value::Bool(report.token_expired())),
+                    ("updatedAt", Value::String(updated_at.clone())),
+                ]),
+            );
+        }
+
+        fs::write(path, python_json_string(&Value::Object(state)))
+            .with_context(|| format!("failed to write state file {}", path.display()))
+    }
+}
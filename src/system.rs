@@ -0,0 +1,122 @@
+// Copyright 2026 YuzakiKokuban
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Time and randomness for the security and signature code, behind traits
+//! so tests can pin both and check payloads byte for byte.
+
+use chrono::{DateTime, FixedOffset, Local, TimeDelta};
+use rsa::rand_core::{self, CryptoRng, OsRng, RngCore};
+use uuid::{Builder, Uuid};
+
+pub trait Clock {
+    /// Current time with the offset used for local-time fields (`smid`).
+    fn now(&self) -> DateTime<FixedOffset>;
+
+    fn unix_millis(&self) -> i64 {
+        self.now().timestamp_millis()
+    }
+
+    fn unix_seconds(&self) -> i64 {
+        self.now().timestamp()
+    }
+}
+
+pub trait RandomSource {
+    fn fill_bytes(&mut self, dest: &mut [u8]);
+
+    fn uuid_v4(&mut self) -> Uuid {
+        let mut bytes = [0_u8; 16];
+        self.fill_bytes(&mut bytes);
+        Builder::from_random_bytes(bytes).into_uuid()
+    }
+}
+
+/// The wall clock in the machine's local time zone.
+pub struct SystemClock;
+
+/// The operating system's CSPRNG.
+pub struct SystemRandom;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<FixedOffset> {
+        Local::now().fixed_offset()
+    }
+}
+
+impl RandomSource for SystemRandom {
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        OsRng.fill_bytes(dest);
+    }
+}
+
+/// Another clock shifted by a fixed number of milliseconds, such as the
+/// local clock corrected to the server's.
+pub struct OffsetClock<'a>(pub &'a dyn Clock, pub i64);
+
+impl Clock for OffsetClock<'_> {
+    fn now(&self) -> DateTime<FixedOffset> {
+        self.0.now() + TimeDelta::milliseconds(self.1)
+    }
+}
+
+/// Lets any `RandomSource` drive APIs that want a `rand_core` 0.6 CSPRNG,
+/// such as RSA padding. Only as strong as the source it wraps.
+pub struct RandCompat<'a>(pub &'a mut dyn RandomSource);
+
+impl RngCore for RandCompat<'_> {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0_u8; 4];
+        self.0.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0_u8; 8];
+        self.0.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.0.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for RandCompat<'_> {}
+
+#[cfg(test)]
+pub mod fixed {
+    use chrono::{DateTime, FixedOffset, TimeZone};
+
+    use super::{Clock, RandomSource};
+
+    /// Always the same instant, in UTC+8 like the Skyland servers.
+    pub struct FixedClock(pub i64);
+
+    /// A byte counter: predictable, and never all zeros, which RSA padding
+    /// would reject.
+    pub struct CountingRandom(pub u8);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> DateTime<FixedOffset> {
+            FixedOffset::east_opt(8 * 3600)
+                .expect("valid offset")
+                .timestamp_millis_opt(self.0)
+                .single()
+                .expect("valid timestamp")
+        }
+    }
+
+    impl RandomSource for CountingRandom {
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            for byte in dest {
+                self.0 = self.0.wrapping_add(1).max(1);
+                *byte = self.0;
+            }
+        }
+    }
+}
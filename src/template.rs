@@ -0,0 +1,311 @@
+// Copyright 2026 YuzakiKokuban
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! A small Mustache-style template language for notification messages.
+//!
+//! * `{{path.to.value}}` inserts a value from the report context.
+//! * `{{#name}}...{{/name}}` repeats for every item of a list, or renders
+//!   once when the value is truthy; `{{^name}}...{{/name}}` renders only
+//!   when it is missing, false or empty.
+//! * `{{.}}` is the current list item.
+//!
+//! Section tags standing alone on a line do not leave blank lines behind.
+
+use anyhow::{Result, bail};
+use serde_json::Value;
+
+use crate::utils::python_json_string;
+
+#[derive(Clone, Debug)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Text(String),
+    Variable(String),
+    Section {
+        name: String,
+        inverted: bool,
+        children: Vec<Node>,
+    },
+}
+
+/// An open section (name, inverted) and the nodes collected inside it; the
+/// bottom frame is the template root.
+type Frame = (Option<(String, bool)>, Vec<Node>);
+
+enum Tag<'a> {
+    Variable(&'a str),
+    Open(&'a str, bool),
+    Close(&'a str),
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut stack: Vec<Frame> = vec![(None, Vec::new())];
+        let mut rest = source;
+        let mut line_start = true;
+
+        while let Some(start) = rest.find("{{") {
+            let Some(length) = rest[start + 2..].find("}}") else {
+                bail!("unclosed tag near: {}", &rest[start..]);
+            };
+            let end = start + 2 + length + 2;
+            let tag = parse_tag(rest[start + 2..end - 2].trim())?;
+
+            let mut text = &rest[..start];
+            let mut after = &rest[end..];
+            let mut standalone = false;
+            if !matches!(tag, Tag::Variable(_)) {
+                (text, after, standalone) = strip_standalone(text, after, line_start);
+            }
+            push_text(&mut stack, text);
+            rest = after;
+            line_start = standalone;
+
+            match tag {
+                Tag::Variable(name) => {
+                    current(&mut stack).push(Node::Variable(name.to_string()));
+                }
+                Tag::Open(name, inverted) => {
+                    stack.push((Some((name.to_string(), inverted)), Vec::new()));
+                }
+                Tag::Close(name) => {
+                    let Some((Some((open, inverted)), children)) = stack.pop() else {
+                        bail!("unexpected closing tag {{{{/{name}}}}}");
+                    };
+                    if open != name {
+                        bail!("section {{{{#{open}}}}} closed by {{{{/{name}}}}}");
+                    }
+                    current(&mut stack).push(Node::Section {
+                        name: open,
+                        inverted,
+                        children,
+                    });
+                }
+            }
+        }
+        push_text(&mut stack, rest);
+
+        if stack.len() != 1 {
+            let open = stack
+                .last()
+                .and_then(|(open, _)| open.as_ref())
+                .map(|(name, _)| name.clone())
+                .unwrap_or_default();
+            bail!("unclosed section {{{{#{open}}}}}");
+        }
+        let (_, nodes) = stack.pop().unwrap_or_default();
+        Ok(Self { nodes })
+    }
+
+    pub fn render(&self, context: &Value) -> String {
+        let mut output = String::new();
+        render_nodes(&self.nodes, &mut vec![context], &mut output);
+        output
+    }
+}
+
+fn parse_tag(tag: &str) -> Result<Tag<'_>> {
+    let (kind, name) = match tag.chars().next() {
+        Some(kind @ ('#' | '^' | '/')) => (Some(kind), tag[1..].trim()),
+        _ => (None, tag),
+    };
+    if name.is_empty() {
+        bail!("empty tag");
+    }
+
+    Ok(match kind {
+        Some('#') => Tag::Open(name, false),
+        Some('^') => Tag::Open(name, true),
+        Some(_) => Tag::Close(name),
+        None => Tag::Variable(name),
+    })
+}
+
+fn current(stack: &mut [Frame]) -> &mut Vec<Node> {
+    &mut stack.last_mut().expect("template stack is never empty").1
+}
+
+fn push_text(stack: &mut [Frame], text: &str) {
+    if !text.is_empty() {
+        current(stack).push(Node::Text(text.to_string()));
+    }
+}
+
+/// Drops the indentation before and the line break after a section tag that
+/// is the only thing on its line. `line_start` tells whether `text` begins at
+/// the start of a source line.
+fn strip_standalone<'a>(
+    text: &'a str,
+    after: &'a str,
+    line_start: bool,
+) -> (&'a str, &'a str, bool) {
+    let line_begin = text.rfind('\n').map(|index| index + 1);
+    let indent = &text[line_begin.unwrap_or(0)..];
+    if !indent.trim().is_empty() || (line_begin.is_none() && !line_start) {
+        return (text, after, false);
+    }
+
+    let line_end = after.find('\n');
+    let trailing = &after[..line_end.unwrap_or(after.len())];
+    if !trailing.trim().is_empty() {
+        return (text, after, false);
+    }
+
+    (
+        &text[..text.len() - indent.len()],
+        &after[line_end.map(|index| index + 1).unwrap_or(after.len())..],
+        true,
+    )
+}
+
+fn render_nodes<'a>(nodes: &'a [Node], stack: &mut Vec<&'a Value>, output: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => output.push_str(text),
+            Node::Variable(name) => {
+                if let Some(value) = lookup(stack, name) {
+                    output.push_str(&render_value(value));
+                }
+            }
+            Node::Section {
+                name,
+                inverted,
+                children,
+            } => {
+                let value = lookup(stack, name);
+                let truthy = value.is_some_and(is_truthy);
+                if *inverted {
+                    if !truthy {
+                        render_nodes(children, stack, output);
+                    }
+                    continue;
+                }
+
+                match value {
+                    Some(Value::Array(items)) => {
+                        for item in items {
+                            stack.push(item);
+                            render_nodes(children, stack, output);
+                            stack.pop();
+                        }
+                    }
+                    Some(value) if truthy => {
+                        stack.push(value);
+                        render_nodes(children, stack, output);
+                        stack.pop();
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+fn lookup<'a>(stack: &[&'a Value], name: &str) -> Option<&'a Value> {
+    if name == "." {
+        return stack.last().copied();
+    }
+
+    let mut parts = name.split('.');
+    let first = parts.next()?;
+    let mut value = stack.iter().rev().find_map(|scope| scope.get(first))?;
+    for part in parts {
+        value = value.get(part)?;
+    }
+    Some(value)
+}
+
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(value) => *value,
+        Value::String(value) => !value.is_empty(),
+        Value::Array(values) => !values.is_empty(),
+        Value::Number(_) | Value::Object(_) => true,
+    }
+}
+
+fn render_value(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(value) => value.clone(),
+        Value::Array(values) => values
+            .iter()
+            .map(render_value)
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => python_json_string(other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn render(source: &str, context: Value) -> String {
+        Template::parse(source).unwrap().render(&context)
+    }
+
+    #[test]
+    fn renders_variables_and_nested_sections() {
+        let context = json!({
+            "run": {"total": 2, "ok": true},
+            "accounts": [
+                {"label": "main", "records": [{"game": "arknights"}, {"game": "endfield"}]},
+                {"label": "alt", "records": []},
+            ],
+            "tags": ["a", "b"],
+        });
+        assert_eq!(
+            render(
+                "{{run.total}} {{ run.ok }} {{missing}}|{{tags}}",
+                context.clone()
+            ),
+            "2 true |a, b"
+        );
+        assert_eq!(
+            render(
+                "{{#accounts}}{{label}}:{{#records}}[{{game}}]{{/records}}{{^records}}none{{/records}};{{/accounts}}",
+                context.clone(),
+            ),
+            "main:[arknights][endfield];alt:none;"
+        );
+        assert_eq!(
+            render(
+                "{{#run}}{{total}}{{/run}}{{#tags}}<{{.}}>{{/tags}}",
+                context.clone()
+            ),
+            "2<a><b>"
+        );
+        assert_eq!(render("{{^run.ok}}down{{/run.ok}}", context), "");
+    }
+
+    #[test]
+    fn strips_standalone_section_lines() {
+        let source = "Report\n  {{#items}}\n- {{.}}\n  {{/items}}\nEnd {{#items}}x{{/items}}\n";
+        assert_eq!(
+            render(source, json!({"items": [1, 2]})),
+            "Report\n- 1\n- 2\nEnd xx\n"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        for source in [
+            "{{name",
+            "{{#a}}open",
+            "{{#a}}{{/b}}",
+            "{{/a}}",
+            "{{}}",
+            "{{#a}}{{^b}}{{/a}}{{/b}}",
+        ] {
+            assert!(Template::parse(source).is_err(), "{source:?} parsed");
+        }
+    }
+}
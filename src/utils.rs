@@ -1,6 +1,10 @@
 // Copyright 2026 YuzakiKokuban
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::io::{self, IsTerminal, Write};
+use std::process::Command;
+
+use anyhow::{Context, Result};
 use serde_json::{Map, Value};
 
 pub fn md5_hex(data: impl AsRef<[u8]>) -> String {
@@ -47,12 +51,81 @@ pub fn python_json_string(value: &Value) -> String {
     }
 }
 
+/// `command` run by the platform shell: `sh -c`, or `cmd /C` on Windows.
+pub fn shell_command(command: &str) -> Command {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut shell = Command::new(shell);
+    shell.args([flag, command]);
+    shell
+}
+
+pub fn env_flag(name: &str) -> bool {
+    std::env::var(name)
+        .map(|value| {
+            matches!(
+                value.trim().to_ascii_lowercase().as_str(),
+                "1" | "true" | "yes" | "on"
+            )
+        })
+        .unwrap_or(false)
+}
+
+/// Keeps the first and last four characters of a secret for log lines;
+/// values too short for that to hide anything are masked entirely.
 pub fn mask_token(token: &str) -> String {
-    if token.len() <= 8 {
-        return format!("{token}***");
+    let chars = token.chars().collect::<Vec<_>>();
+    if chars.len() <= 8 {
+        return "***".to_string();
     }
 
-    let prefix = &token[..4];
-    let suffix = &token[token.len() - 4..];
+    let prefix = chars[..4].iter().collect::<String>();
+    let suffix = chars[chars.len() - 4..].iter().collect::<String>();
     format!("{prefix}***{suffix}")
 }
+
+/// Asks on stderr and reads one line from stdin, so stdout stays clean for
+/// whatever the command prints.
+pub fn prompt(label: &str) -> Result<String> {
+    eprint!("{label}");
+    io::stderr().flush().ok();
+    let mut line = String::new();
+    io::stdin()
+        .read_line(&mut line)
+        .context("failed to read from stdin")?;
+    non_empty_answer(label, &line)
+}
+
+/// Like `prompt`, but the answer is not echoed when stdin is a terminal.
+/// Piped input is read as usual.
+pub fn prompt_secret(label: &str) -> Result<String> {
+    if !io::stdin().is_terminal() {
+        return prompt(label);
+    }
+    let line = rpassword::prompt_password(label).context("failed to read from the terminal")?;
+    non_empty_answer(label, &line)
+}
+
+fn non_empty_answer(label: &str, line: &str) -> Result<String> {
+    let value = line.trim().to_string();
+    if value.is_empty() {
+        anyhow::bail!("no input for {}", label.trim_end_matches([':', ' ']));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_tokens_on_char_boundaries() {
+        assert_eq!(mask_token("0123456789abcdef"), "0123***cdef");
+        assert_eq!(mask_token("12345678"), "***");
+        assert_eq!(mask_token(""), "***");
+        assert_eq!(mask_token("密码密码密码密码口令"), "密码密码***密码口令");
+    }
+}
@@ -0,0 +1,412 @@
+// Copyright 2026 YuzakiKokuban
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Encrypted account store for long-running setups that should not keep
+//! tokens in the environment. The file is JSON with a PBKDF2-HMAC-SHA256
+//! salt and an AES-256-GCM sealed list of accounts; the key is derived from
+//! a passphrase that never touches the disk.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{Context, Result, anyhow, bail};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::Value;
+use sha2::Sha256;
+use tracing::info;
+
+use crate::config::{Account, Credential, non_empty_env, register_credential};
+use crate::redact::register;
+use crate::secrets::secret_env;
+use crate::utils::{object_from_pairs, prompt_secret, python_json_string};
+
+const VERSION: u64 = 1;
+const KDF: &str = "pbkdf2-sha256";
+/// Tests only check the format, not the work factor.
+const ITERATIONS: u32 = if cfg!(test) { 1_000 } else { 600_000 };
+/// Bound into the GCM tag so a ciphertext cannot be replayed under another
+/// format version.
+const AAD: &[u8] = b"skas-vault-v1";
+
+pub struct Vault {
+    path: PathBuf,
+    key: [u8; 32],
+    salt: [u8; 16],
+    iterations: u32,
+    pub accounts: Vec<Account>,
+}
+
+impl Vault {
+    /// Creates an empty vault in memory; nothing is written until `save`.
+    pub fn create(path: impl Into<PathBuf>, passphrase: &str) -> Self {
+        let salt = rand::random::<[u8; 16]>();
+        Self {
+            path: path.into(),
+            key: derive_key(passphrase, &salt, ITERATIONS),
+            salt,
+            iterations: ITERATIONS,
+            accounts: Vec::new(),
+        }
+    }
+
+    pub fn open(path: impl Into<PathBuf>, passphrase: &str) -> Result<Self> {
+        let path = path.into();
+        let raw = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read vault {}", path.display()))?;
+        let file = serde_json::from_str::<Value>(&raw)
+            .with_context(|| format!("vault {} is not valid JSON", path.display()))?;
+
+        let version = file.get("version").and_then(Value::as_u64);
+        if version != Some(VERSION) {
+            bail!("unsupported vault version {version:?}");
+        }
+        let kdf = file.get("kdf").and_then(Value::as_str).unwrap_or_default();
+        if kdf != KDF {
+            bail!("unsupported vault kdf {kdf:?}");
+        }
+        let iterations = file
+            .get("iterations")
+            .and_then(Value::as_u64)
+            .and_then(|value| u32::try_from(value).ok())
+            .filter(|value| *value > 0)
+            .context("vault has no valid iterations")?;
+        let salt = decode_field::<16>(&file, "salt")?;
+        let nonce = decode_field::<12>(&file, "nonce")?;
+        let data = STANDARD
+            .decode(file.get("data").and_then(Value::as_str).unwrap_or_default())
+            .context("vault data is not valid base64")?;
+
+        let key = derive_key(passphrase, &salt, iterations);
+        let plaintext = Aes256Gcm::new(&Key::<Aes256Gcm>::from(key))
+            .decrypt(
+                &Nonce::from(nonce),
+                Payload {
+                    msg: &data,
+                    aad: AAD,
+                },
+            )
+            .map_err(|_| anyhow!("wrong passphrase or corrupted vault"))?;
+        let accounts = parse_accounts(&plaintext)?;
+
+        Ok(Self {
+            path,
+            key,
+            salt,
+            iterations,
+            accounts,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Adds the account or replaces the one with the same label; returns
+    /// whether it replaced one.
+    pub fn upsert(&mut self, account: Account) -> bool {
+        match self
+            .accounts
+            .iter_mut()
+            .find(|existing| existing.label == account.label)
+        {
+            Some(existing) => {
+                *existing = account;
+                true
+            }
+            None => {
+                self.accounts.push(account);
+                false
+            }
+        }
+    }
+
+    pub fn remove(&mut self, label: &str) -> bool {
+        let before = self.accounts.len();
+        self.accounts.retain(|account| account.label != label);
+        self.accounts.len() != before
+    }
+
+    /// Switches to a new passphrase with a fresh salt; takes effect on `save`.
+    pub fn rekey(&mut self, passphrase: &str) {
+        self.salt = rand::random::<[u8; 16]>();
+        self.iterations = ITERATIONS;
+        self.key = derive_key(passphrase, &self.salt, self.iterations);
+    }
+
+    /// Seals the accounts under a fresh nonce and replaces the file through a
+    /// temporary sibling, so an interrupted write never leaves half a vault.
+    pub fn save(&self) -> Result<()> {
+        let nonce = rand::random::<[u8; 12]>();
+        let plaintext = python_json_string(&accounts_value(&self.accounts));
+        let data = Aes256Gcm::new(&Key::<Aes256Gcm>::from(self.key))
+            .encrypt(
+                &Nonce::from(nonce),
+                Payload {
+                    msg: plaintext.as_bytes(),
+                    aad: AAD,
+                },
+            )
+            .map_err(|_| anyhow!("failed to encrypt vault"))?;
+
+        let file = object_from_pairs([
+            ("version", Value::from(VERSION)),
+            ("kdf", Value::from(KDF)),
+            ("iterations", Value::from(self.iterations)),
+            ("salt", Value::from(STANDARD.encode(self.salt))),
+            ("nonce", Value::from(STANDARD.encode(nonce))),
+            ("data", Value::from(STANDARD.encode(data))),
+        ]);
+
+        let temp = self.path.with_extension("tmp");
+        write_private(&temp, format!("{}\n", python_json_string(&file)).as_bytes())?;
+        fs::rename(&temp, &self.path)
+            .with_context(|| format!("failed to replace vault {}", self.path.display()))
+    }
+}
+
+/// `VAULT_FILE`, if set.
+pub fn vault_path() -> Option<String> {
+    non_empty_env("VAULT_FILE")
+}
+
+/// `VAULT_PASSPHRASE` (any secret source works), else asked on stderr
+/// without echo, twice when `confirm` is set.
+pub fn vault_passphrase(confirm: bool) -> Result<String> {
+    let passphrase = match secret_env("VAULT_PASSPHRASE")? {
+        Some(passphrase) => passphrase,
+        None => {
+            let passphrase = prompt_secret("Vault passphrase: ")?;
+            if confirm && prompt_secret("Repeat passphrase: ")? != passphrase {
+                bail!("passphrases do not match");
+            }
+            passphrase
+        }
+    };
+    register(&passphrase);
+    Ok(passphrase)
+}
+
+/// Accounts from `VAULT_FILE`, or none when no vault is configured.
+pub fn load_vault_accounts() -> Result<Vec<Account>> {
+    let Some(path) = vault_path() else {
+        return Ok(Vec::new());
+    };
+    let vault = Vault::open(&path, &vault_passphrase(false)?)?;
+    for account in &vault.accounts {
+        register_credential(&account.credential);
+    }
+    info!(
+        "Loaded {} account(s) from vault {path}",
+        vault.accounts.len()
+    );
+    Ok(vault.accounts)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> [u8; 32] {
+    pbkdf2::pbkdf2_hmac_array::<Sha256, 32>(passphrase.as_bytes(), salt, iterations)
+}
+
+fn decode_field<const N: usize>(file: &Value, key: &str) -> Result<[u8; N]> {
+    let raw = file.get(key).and_then(Value::as_str).unwrap_or_default();
+    STANDARD
+        .decode(raw)
+        .ok()
+        .and_then(|bytes| <[u8; N]>::try_from(bytes).ok())
+        .with_context(|| format!("vault has no valid {key}"))
+}
+
+fn accounts_value(accounts: &[Account]) -> Value {
+    let entries = accounts
+        .iter()
+        .map(|account| match &account.credential {
+            Credential::Token(token) => object_from_pairs([
+                ("label", Value::from(account.label.as_str())),
+                ("token", Value::from(token.as_str())),
+            ]),
+            Credential::Cred { cred, token } => object_from_pairs([
+                ("label", Value::from(account.label.as_str())),
+                ("cred", Value::from(cred.as_str())),
+                ("credToken", Value::from(token.as_str())),
+            ]),
+        })
+        .collect::<Vec<_>>();
+    object_from_pairs([("accounts", Value::Array(entries))])
+}
+
+fn parse_accounts(plaintext: &[u8]) -> Result<Vec<Account>> {
+    let value =
+        serde_json::from_slice::<Value>(plaintext).context("vault contents are not valid JSON")?;
+    let entries = value
+        .get("accounts")
+        .and_then(Value::as_array)
+        .context("vault contents have no accounts")?;
+
+    entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let field = |key: &str| entry.get(key).and_then(Value::as_str).map(str::to_string);
+            let label = field("label")
+                .with_context(|| format!("vault account #{} has no label", index + 1))?;
+            let credential = match (field("token"), field("cred"), field("credToken")) {
+                (Some(token), None, None) => Credential::Token(token),
+                (None, Some(cred), Some(token)) => Credential::Cred { cred, token },
+                _ => bail!("vault account {label} has neither token nor cred/credToken"),
+            };
+            Ok(Account { label, credential })
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+fn restrict_permissions(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("failed to restrict permissions of {}", path.display()))
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Writes `contents` to a file only its owner can read, creating it with
+/// those permissions rather than tightening them afterwards.
+pub fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    // `mode` only applies to new files.
+    if fs::metadata(path).is_ok() {
+        restrict_permissions(path)?;
+    }
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(contents))
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_vault(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("skas-vault-{name}-{}.json", std::process::id()))
+    }
+
+    fn sample_vault(path: &Path, passphrase: &str) -> Vault {
+        let mut vault = Vault::create(path, passphrase);
+        vault.upsert(Account {
+            label: "main".to_string(),
+            credential: Credential::Token("token-main-0123456789".to_string()),
+        });
+        vault.upsert(Account {
+            label: "alt".to_string(),
+            credential: Credential::Cred {
+                cred: "cred-alt-0123456789".to_string(),
+                token: "cred-token-0123456789".to_string(),
+            },
+        });
+        vault.save().unwrap();
+        vault
+    }
+
+    #[test]
+    fn round_trips_and_rejects_wrong_passphrase() {
+        let path = temp_vault("round-trip");
+        sample_vault(&path, "correct horse");
+        assert!(!fs::read_to_string(&path).unwrap().contains("token-main"));
+
+        let vault = Vault::open(&path, "correct horse").unwrap();
+        let labels = vault
+            .accounts
+            .iter()
+            .map(|account| (account.label.as_str(), account.credential.secret()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            [
+                ("main", "token-main-0123456789"),
+                ("alt", "cred-alt-0123456789")
+            ]
+        );
+        assert!(Vault::open(&path, "wrong horse").is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_tampered_ciphertext_and_aad() {
+        let path = temp_vault("tamper");
+        let vault = sample_vault(&path, "passphrase");
+        let file = serde_json::from_str::<Value>(&fs::read_to_string(&path).unwrap()).unwrap();
+        let mut data = STANDARD.decode(file["data"].as_str().unwrap()).unwrap();
+        data[0] ^= 1;
+        let mut tampered = file.clone();
+        tampered["data"] = Value::from(STANDARD.encode(&data));
+        fs::write(&path, python_json_string(&tampered)).unwrap();
+        assert!(Vault::open(&path, "passphrase").is_err());
+
+        // The same ciphertext sealed under another format tag.
+        let nonce = rand::random::<[u8; 12]>();
+        let sealed = Aes256Gcm::new(&Key::<Aes256Gcm>::from(vault.key))
+            .encrypt(
+                &Nonce::from(nonce),
+                Payload {
+                    msg: b"[]",
+                    aad: b"skas-vault-v0",
+                },
+            )
+            .unwrap();
+        let mut other_aad = file;
+        other_aad["nonce"] = Value::from(STANDARD.encode(nonce));
+        other_aad["data"] = Value::from(STANDARD.encode(sealed));
+        fs::write(&path, python_json_string(&other_aad)).unwrap();
+        assert!(Vault::open(&path, "passphrase").is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rekey_switches_passphrase() {
+        let path = temp_vault("rekey");
+        let mut vault = sample_vault(&path, "old passphrase");
+        vault.rekey("new passphrase");
+        vault.save().unwrap();
+
+        assert!(Vault::open(&path, "old passphrase").is_err());
+        let reopened = Vault::open(&path, "new passphrase").unwrap();
+        assert_eq!(reopened.accounts.len(), 2);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private_files_are_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("skas-private-{}", std::process::id()));
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        write_private(&path, b"token\n").unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "token\n");
+        fs::remove_file(&path).unwrap();
+
+        // A leftover temp file from an interrupted save is tightened too.
+        let vault_path = temp_vault("private");
+        let temp = vault_path.with_extension("tmp");
+        fs::write(&temp, "stale").unwrap();
+        fs::set_permissions(&temp, fs::Permissions::from_mode(0o644)).unwrap();
+        sample_vault(&vault_path, "passphrase");
+        let mode = fs::metadata(&vault_path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+        fs::remove_file(&vault_path).unwrap();
+    }
+}